@@ -0,0 +1,55 @@
+//! A checkpoint store that keeps the last committed state version in a plain text file.
+
+use crate::checkpoints::CheckpointStore;
+use async_trait::async_trait;
+use std::path::PathBuf;
+
+/// A [`CheckpointStore`] that writes the last committed state version
+/// to a file. The file is replaced atomically on every save by writing
+/// to a temporary file first and renaming it afterwards.
+#[derive(Debug, Clone)]
+pub struct FileCheckpointStore {
+    path: PathBuf,
+}
+
+impl FileCheckpointStore {
+    /// Creates a new store which reads and writes the given file.
+    /// The file doesn't have to exist yet.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    fn temporary_path(&self) -> PathBuf {
+        let mut path = self.path.clone().into_os_string();
+        path.push(".tmp");
+        path.into()
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for FileCheckpointStore {
+    async fn load(&self) -> Result<Option<u64>, anyhow::Error> {
+        let contents = match tokio::fs::read_to_string(&self.path).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(None)
+            }
+            Err(err) => return Err(err.into()),
+        };
+        let state_version = contents.trim().parse::<u64>().map_err(|err| {
+            anyhow::anyhow!(
+                "Invalid checkpoint in {}: {}",
+                self.path.display(),
+                err
+            )
+        })?;
+        Ok(Some(state_version))
+    }
+
+    async fn save(&mut self, state_version: u64) -> Result<(), anyhow::Error> {
+        let temporary_path = self.temporary_path();
+        tokio::fs::write(&temporary_path, state_version.to_string()).await?;
+        tokio::fs::rename(&temporary_path, &self.path).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,42 @@
+//! Stores that persist the last state version which was fully processed
+//! by a [`TransactionProcessor`][crate::processor::TransactionProcessor].
+//!
+//! When a [`CheckpointStore`] is set on the processor, it is updated after
+//! each transaction is finished. When the processor is started again, it
+//! loads the last committed state version from the store and tells the
+//! [`TransactionStream`][crate::stream::TransactionStream] to resume
+//! from the state version right after it, so a restarted indexer continues
+//! exactly where it stopped.
+//!
+//! A file based store is always available. The SQLite and PostgreSQL stores
+//! can be enabled with the `sqlite` and `database` feature flags.
+
+use async_trait::async_trait;
+
+pub mod file;
+#[cfg(feature = "database")]
+pub mod postgres;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// A trait that abstracts a persistent store for the last state version
+/// that was fully processed.
+///
+/// Implementations should make [`CheckpointStore::save`] durable before
+/// returning, because the processor considers the transaction committed
+/// once the call succeeds.
+#[async_trait]
+pub trait CheckpointStore: Send + Sync {
+    /// Loads the last committed state version, or `None` if
+    /// nothing has been committed yet.
+    async fn load(&self) -> Result<Option<u64>, anyhow::Error>;
+
+    /// Persists `state_version` as the last committed state version.
+    async fn save(&mut self, state_version: u64) -> Result<(), anyhow::Error>;
+
+    /// Returns the state version a stream should resume from, which is the
+    /// one right after the last committed state version.
+    async fn resume_state_version(&self) -> Result<Option<u64>, anyhow::Error> {
+        Ok(self.load().await?.map(|state_version| state_version + 1))
+    }
+}
@@ -0,0 +1,80 @@
+//! A checkpoint store that keeps the last committed state version in a PostgreSQL database.
+
+use crate::checkpoints::CheckpointStore;
+use async_trait::async_trait;
+use sqlx::{Pool, Postgres};
+
+/// A [`CheckpointStore`] backed by a PostgreSQL table. Multiple processors
+/// can share the same table, as each checkpoint is identified by a name.
+#[derive(Debug, Clone)]
+pub struct PostgresCheckpointStore {
+    pool: Pool<Postgres>,
+    name: String,
+}
+
+impl PostgresCheckpointStore {
+    /// Creates a new store on an existing connection pool. The
+    /// `event_stream_checkpoints` table is created if it doesn't exist yet.
+    pub async fn new(
+        pool: Pool<Postgres>,
+        name: impl Into<String>,
+    ) -> Result<Self, anyhow::Error> {
+        sqlx::query(
+            r#"
+                CREATE TABLE IF NOT EXISTS event_stream_checkpoints (
+                    name TEXT PRIMARY KEY,
+                    state_version BIGINT NOT NULL
+                )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+        Ok(Self {
+            pool,
+            name: name.into(),
+        })
+    }
+
+    /// Connects to the database at `database_url` and creates a new store on it.
+    pub async fn connect(
+        database_url: &str,
+        name: impl Into<String>,
+    ) -> Result<Self, anyhow::Error> {
+        let pool = sqlx::postgres::PgPoolOptions::new()
+            .connect(database_url)
+            .await?;
+        Self::new(pool, name).await
+    }
+}
+
+#[async_trait]
+impl CheckpointStore for PostgresCheckpointStore {
+    async fn load(&self) -> Result<Option<u64>, anyhow::Error> {
+        let state_version: Option<i64> = sqlx::query_scalar(
+            r#"
+                SELECT state_version
+                FROM event_stream_checkpoints
+                WHERE name = $1
+            "#,
+        )
+        .bind(&self.name)
+        .fetch_optional(&self.pool)
+        .await?;
+        Ok(state_version.map(|state_version| state_version as u64))
+    }
+
+    async fn save(&mut self, state_version: u64) -> Result<(), anyhow::Error> {
+        sqlx::query(
+            r#"
+                INSERT INTO event_stream_checkpoints (name, state_version)
+                VALUES ($1, $2)
+                ON CONFLICT (name) DO UPDATE SET state_version = EXCLUDED.state_version
+            "#,
+        )
+        .bind(&self.name)
+        .bind(state_version as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
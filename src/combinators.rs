@@ -0,0 +1,498 @@
+//! Combinators that wrap a [`TransactionStream`] into a new one,
+//! available on every stream through [`TransactionStreamExt`].
+//!
+//! For example, to reprocess a fixed range of state versions:
+//! ```ignore
+//! let stream = DatabaseTransactionStream::new(database_url)
+//!     .from_state_version(from)
+//!     .take_until_state_version(to)
+//!     .filter(|transaction| transaction.status.is_success());
+//! ```
+//!
+//! Each combinator starts the stream it wraps and forwards its transactions
+//! from a separate task. Stopping a combinator also stops the wrapped stream,
+//! and resuming from a checkpoint is passed on to the wrapped stream.
+
+use crate::{
+    logger::Logger,
+    models::Transaction,
+    stream::{StreamErrorSlot, TransactionStream},
+};
+use async_trait::async_trait;
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::sync::{mpsc::Receiver, RwLock};
+
+/// The capacity of the channel between a combinator and the processor.
+/// The wrapped stream already buffers, so this can be small.
+const BUFFER_CAPACITY: usize = 32;
+
+/// What a combinator does with a transaction of the wrapped stream.
+enum Step {
+    Emit(Transaction),
+    /// Emits the transaction and ends the stream.
+    EmitLast(Transaction),
+    Skip,
+    End,
+}
+
+/// The wrapped stream and the task which forwards its transactions,
+/// shared by the combinators.
+#[derive(Debug)]
+struct Forwarder<S> {
+    stream: S,
+    join_handle: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl<S: TransactionStream + Send> Forwarder<S> {
+    fn new(stream: S) -> Self {
+        Self {
+            stream,
+            join_handle: None,
+        }
+    }
+
+    /// Starts the wrapped stream and forwards each transaction through `step`.
+    async fn start(
+        &mut self,
+        mut step: impl FnMut(Transaction) -> Step + Send + 'static,
+    ) -> Result<Receiver<Transaction>, anyhow::Error> {
+        let mut receiver = self.stream.start().await?;
+        let (tx, rx) = tokio::sync::mpsc::channel(BUFFER_CAPACITY);
+        self.join_handle = Some(tokio::spawn(async move {
+            while let Some(transaction) = receiver.recv().await {
+                let (transaction, last) = match step(transaction) {
+                    Step::Emit(transaction) => (transaction, false),
+                    Step::EmitLast(transaction) => (transaction, true),
+                    Step::Skip => continue,
+                    Step::End => return,
+                };
+                if tx.send(transaction).await.is_err() || last {
+                    return;
+                }
+            }
+        }));
+        Ok(rx)
+    }
+
+    async fn stop(&mut self) {
+        if let Some(handle) = self.join_handle.take() {
+            handle.abort();
+        }
+        self.stream.stop().await;
+    }
+}
+
+/// Implements [`TransactionStream`] for a combinator with a [`Forwarder`]
+/// in its `forwarder` field, given the function which creates its step.
+/// The state version the combinator reached is the one of the wrapped
+/// stream, unless a function which returns it is given too.
+macro_rules! forwarding_stream {
+    ($combinator:ident<S $(, $param:ident: $bound:path)?>, |$self:ident| $step:expr) => {
+        forwarding_stream!(
+            $combinator<S $(, $param: $bound)?>,
+            |$self| $step,
+            |$self| $self.forwarder.stream.reached_state_version()
+        );
+    };
+    (
+        $combinator:ident<S $(, $param:ident: $bound:path)?>,
+        |$self:ident| $step:expr,
+        |$reached_self:ident| $reached:expr
+    ) => {
+        #[async_trait]
+        impl<S: TransactionStream + Send $(, $param: $bound)?> TransactionStream
+            for $combinator<S $(, $param)?>
+        {
+            async fn start(
+                &mut $self,
+            ) -> Result<Receiver<Transaction>, anyhow::Error> {
+                let step = $step;
+                $self.forwarder.start(step).await
+            }
+
+            async fn stop(&mut self) {
+                self.forwarder.stop().await;
+            }
+
+            fn resume_from_state_version(&mut self, state_version: u64) {
+                self.forwarder.stream.resume_from_state_version(state_version);
+            }
+
+            fn set_logger(&mut self, logger: Arc<RwLock<Box<dyn Logger>>>) {
+                self.forwarder.stream.set_logger(logger);
+            }
+
+            fn take_error(&mut self) -> Option<anyhow::Error> {
+                self.forwarder.stream.take_error()
+            }
+
+            fn reached_state_version(&$reached_self) -> Option<u64> {
+                $reached
+            }
+        }
+
+        impl<S: fmt::Debug $(, $param)?> fmt::Debug for $combinator<S $(, $param)?> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.debug_struct(stringify!($combinator))
+                    .field("stream", &self.forwarder.stream)
+                    .finish_non_exhaustive()
+            }
+        }
+    };
+}
+
+/// A stream that only emits the transactions matching a predicate.
+/// See [`TransactionStreamExt::filter`].
+pub struct Filter<S, F> {
+    forwarder: Forwarder<S>,
+    predicate: Arc<F>,
+}
+
+/// A predicate on a transaction.
+pub trait TransactionPredicate:
+    Fn(&Transaction) -> bool + Send + Sync + 'static
+{
+}
+impl<F: Fn(&Transaction) -> bool + Send + Sync + 'static> TransactionPredicate
+    for F
+{
+}
+
+forwarding_stream!(Filter<S, F: TransactionPredicate>, |self| {
+    let predicate = self.predicate.clone();
+    move |transaction: Transaction| {
+        if predicate(&transaction) {
+            Step::Emit(transaction)
+        } else {
+            Step::Skip
+        }
+    }
+});
+
+/// A stream that transforms each transaction.
+/// See [`TransactionStreamExt::map`].
+pub struct Map<S, F> {
+    forwarder: Forwarder<S>,
+    map: Arc<F>,
+}
+
+/// A function that transforms a transaction.
+pub trait TransactionMap:
+    Fn(Transaction) -> Transaction + Send + Sync + 'static
+{
+}
+impl<F: Fn(Transaction) -> Transaction + Send + Sync + 'static> TransactionMap
+    for F
+{
+}
+
+forwarding_stream!(Map<S, F: TransactionMap>, |self| {
+    let map = self.map.clone();
+    move |transaction: Transaction| Step::Emit(map(transaction))
+});
+
+/// A stream that calls a function with each transaction before emitting it.
+/// See [`TransactionStreamExt::tap`].
+pub struct Tap<S, F> {
+    forwarder: Forwarder<S>,
+    tap: Arc<F>,
+}
+
+/// A function that inspects a transaction.
+pub trait TransactionInspector:
+    Fn(&Transaction) + Send + Sync + 'static
+{
+}
+impl<F: Fn(&Transaction) + Send + Sync + 'static> TransactionInspector for F {}
+
+forwarding_stream!(Tap<S, F: TransactionInspector>, |self| {
+    let tap = self.tap.clone();
+    move |transaction: Transaction| {
+        tap(&transaction);
+        Step::Emit(transaction)
+    }
+});
+
+/// A stream that skips transactions until one matches a predicate,
+/// and emits all transactions from there on.
+/// See [`TransactionStreamExt::skip_until`].
+pub struct SkipUntil<S, F> {
+    forwarder: Forwarder<S>,
+    predicate: Arc<F>,
+}
+
+forwarding_stream!(SkipUntil<S, F: TransactionPredicate>, |self| {
+    let predicate = self.predicate.clone();
+    let mut skipping = true;
+    move |transaction: Transaction| {
+        skipping = skipping && !predicate(&transaction);
+        if skipping {
+            Step::Skip
+        } else {
+            Step::Emit(transaction)
+        }
+    }
+});
+
+/// A stream that ends after the transaction at a state version.
+/// See [`TransactionStreamExt::take_until_state_version`].
+pub struct TakeUntilStateVersion<S> {
+    forwarder: Forwarder<S>,
+    state_version: u64,
+}
+
+forwarding_stream!(
+    TakeUntilStateVersion<S>,
+    |self| {
+        let last_state_version = self.state_version;
+        move |transaction: Transaction| match transaction
+            .state_version
+            .cmp(&last_state_version)
+        {
+            std::cmp::Ordering::Less => Step::Emit(transaction),
+            std::cmp::Ordering::Equal => Step::EmitLast(transaction),
+            std::cmp::Ordering::Greater => Step::End,
+        }
+    },
+    |self| {
+        self.forwarder
+            .stream
+            .reached_state_version()
+            .map(|reached| reached.min(self.state_version))
+    }
+);
+
+/// A stream that emits at most one transaction per interval.
+/// See [`TransactionStreamExt::throttle`].
+#[derive(Debug)]
+pub struct Throttle<S> {
+    stream: S,
+    interval: Duration,
+    join_handle: Option<tokio::task::JoinHandle<()>>,
+}
+
+#[async_trait]
+impl<S: TransactionStream + Send> TransactionStream for Throttle<S> {
+    async fn start(&mut self) -> Result<Receiver<Transaction>, anyhow::Error> {
+        let mut receiver = self.stream.start().await?;
+        let (tx, rx) = tokio::sync::mpsc::channel(BUFFER_CAPACITY);
+        let mut interval = tokio::time::interval(self.interval);
+        // Don't make up for time spent waiting on the wrapped stream.
+        interval
+            .set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        self.join_handle = Some(tokio::spawn(async move {
+            while let Some(transaction) = receiver.recv().await {
+                interval.tick().await;
+                if tx.send(transaction).await.is_err() {
+                    return;
+                }
+            }
+        }));
+        Ok(rx)
+    }
+
+    async fn stop(&mut self) {
+        if let Some(handle) = self.join_handle.take() {
+            handle.abort();
+        }
+        self.stream.stop().await;
+    }
+
+    fn resume_from_state_version(&mut self, state_version: u64) {
+        self.stream.resume_from_state_version(state_version);
+    }
+
+    fn set_logger(&mut self, logger: Arc<RwLock<Box<dyn Logger>>>) {
+        self.stream.set_logger(logger);
+    }
+
+    fn take_error(&mut self) -> Option<anyhow::Error> {
+        self.stream.take_error()
+    }
+
+    fn reached_state_version(&self) -> Option<u64> {
+        self.stream.reached_state_version()
+    }
+}
+
+/// A stream that emits all transactions of one stream, then all of another.
+/// See [`TransactionStreamExt::chain`].
+#[derive(Debug)]
+pub struct Chain<S, T> {
+    first: Arc<tokio::sync::Mutex<S>>,
+    second: Arc<tokio::sync::Mutex<T>>,
+    error: StreamErrorSlot,
+    join_handle: Option<tokio::task::JoinHandle<()>>,
+}
+
+#[async_trait]
+impl<S, T> TransactionStream for Chain<S, T>
+where
+    S: TransactionStream + Send + 'static,
+    T: TransactionStream + Send + 'static,
+{
+    async fn start(&mut self) -> Result<Receiver<Transaction>, anyhow::Error> {
+        let mut receiver = self.first.lock().await.start().await?;
+        let (tx, rx) = tokio::sync::mpsc::channel(BUFFER_CAPACITY);
+        let first = self.first.clone();
+        let second = self.second.clone();
+        let error = self.error.clone();
+        self.join_handle = Some(tokio::spawn(async move {
+            while let Some(transaction) = receiver.recv().await {
+                if tx.send(transaction).await.is_err() {
+                    return;
+                }
+            }
+            let first_error = {
+                let mut first = first.lock().await;
+                first.stop().await;
+                first.take_error()
+            };
+            // The second stream is only started once the first one ended,
+            // and not when the first one gave up.
+            if let Some(err) = first_error {
+                error.set(err);
+                return;
+            }
+            let mut receiver = match second.lock().await.start().await {
+                Ok(receiver) => receiver,
+                Err(err) => {
+                    error.set(err.context(
+                        "Failed to start the second stream of a chain",
+                    ));
+                    return;
+                }
+            };
+            while let Some(transaction) = receiver.recv().await {
+                if tx.send(transaction).await.is_err() {
+                    return;
+                }
+            }
+            if let Some(err) = second.lock().await.take_error() {
+                error.set(err);
+            }
+        }));
+        Ok(rx)
+    }
+
+    async fn stop(&mut self) {
+        if let Some(handle) = self.join_handle.take() {
+            handle.abort();
+        }
+        self.first.lock().await.stop().await;
+        self.second.lock().await.stop().await;
+    }
+
+    fn resume_from_state_version(&mut self, state_version: u64) {
+        // Resuming happens before the stream is started,
+        // so the locks are never held here.
+        if let Ok(mut first) = self.first.try_lock() {
+            first.resume_from_state_version(state_version);
+        }
+        if let Ok(mut second) = self.second.try_lock() {
+            second.resume_from_state_version(state_version);
+        }
+    }
+
+    fn set_logger(&mut self, logger: Arc<RwLock<Box<dyn Logger>>>) {
+        if let Ok(mut first) = self.first.try_lock() {
+            first.set_logger(logger.clone());
+        }
+        if let Ok(mut second) = self.second.try_lock() {
+            second.set_logger(logger);
+        }
+    }
+
+    fn take_error(&mut self) -> Option<anyhow::Error> {
+        self.error.take()
+    }
+
+    // The second stream ends the chain, and it isn't locked once it ended.
+    fn reached_state_version(&self) -> Option<u64> {
+        self.second.try_lock().ok()?.reached_state_version()
+    }
+}
+
+/// Combinators for every [`TransactionStream`]. See the
+/// [module documentation][crate::combinators] for an example.
+pub trait TransactionStreamExt: TransactionStream + Send + Sized {
+    /// Only emits the transactions for which `predicate` returns true.
+    fn filter<F: TransactionPredicate>(self, predicate: F) -> Filter<Self, F> {
+        Filter {
+            forwarder: Forwarder::new(self),
+            predicate: Arc::new(predicate),
+        }
+    }
+
+    /// Transforms each transaction with `map`.
+    fn map<F: TransactionMap>(self, map: F) -> Map<Self, F> {
+        Map {
+            forwarder: Forwarder::new(self),
+            map: Arc::new(map),
+        }
+    }
+
+    /// Ends the stream after the transaction at `state_version`, or at the
+    /// first transaction after it. Together with a stream's `from_state_version`,
+    /// this bounds the stream to a range of state versions.
+    fn take_until_state_version(
+        self,
+        state_version: u64,
+    ) -> TakeUntilStateVersion<Self> {
+        TakeUntilStateVersion {
+            forwarder: Forwarder::new(self),
+            state_version,
+        }
+    }
+
+    /// Skips transactions until `predicate` returns true for one, and
+    /// emits that transaction and all transactions after it.
+    fn skip_until<F: TransactionPredicate>(
+        self,
+        predicate: F,
+    ) -> SkipUntil<Self, F> {
+        SkipUntil {
+            forwarder: Forwarder::new(self),
+            predicate: Arc::new(predicate),
+        }
+    }
+
+    /// Emits at most one transaction per `interval`.
+    fn throttle(self, interval: Duration) -> Throttle<Self> {
+        Throttle {
+            stream: self,
+            interval,
+            join_handle: None,
+        }
+    }
+
+    /// Calls `tap` with each transaction before it's emitted,
+    /// for example to collect metrics.
+    fn tap<F: TransactionInspector>(self, tap: F) -> Tap<Self, F> {
+        Tap {
+            forwarder: Forwarder::new(self),
+            tap: Arc::new(tap),
+        }
+    }
+
+    /// Emits all transactions of this stream, and when it ends, all
+    /// transactions of `next`. Resuming from a checkpoint is passed
+    /// on to both streams. When this stream gives up on its source,
+    /// `next` isn't started and the chain fails with the error.
+    fn chain<T: TransactionStream + Send + 'static>(
+        self,
+        next: T,
+    ) -> Chain<Self, T>
+    where
+        Self: 'static,
+    {
+        Chain {
+            first: Arc::new(tokio::sync::Mutex::new(self)),
+            second: Arc::new(tokio::sync::Mutex::new(next)),
+            error: StreamErrorSlot::default(),
+            join_handle: None,
+        }
+    }
+}
+
+impl<S: TransactionStream + Send> TransactionStreamExt for S {}
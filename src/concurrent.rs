@@ -0,0 +1,497 @@
+/*!
+# Concurrent Transaction Stream Processor
+
+By default, the [`TransactionStreamProcessor`][crate::processor::TransactionStreamProcessor]
+handles one transaction at a time. When the handlers are the bottleneck, for example
+while backfilling from a database, the [`ConcurrentTransactionStreamProcessor`] can be
+used to handle events of independent emitters in parallel.
+
+Events are sharded over a fixed number of workers by a [`PartitionKey`], which is the
+emitter address by default. Each worker runs on its own task with its own
+[`TransactionProcessor`], so it has its own state, handler registry and logger.
+Events with the same key always go to the same worker, in ledger order, so the
+ordering per key is the same as with the sequential processor. Events with
+different keys can be handled out of order relative to each other.
+
+Because handlers of different workers don't see each other's state or handler registry,
+handlers that register new handlers at runtime only work when the new handlers
+are for events with the same key. In other cases, register the handlers up
+front, for example using an [`EventMatcher::Blueprint`][crate::event_matcher::EventMatcher::Blueprint].
+
+Progress is reported as a watermark: the state version up to which all
+transactions have been fully processed by all workers. It only moves forward,
+and it is what's written to the [`CheckpointStore`].
+*/
+
+use crate::{
+    checkpoints::CheckpointStore,
+    error::TransactionProcessorError,
+    event_handler::State,
+    models::{Event, Transaction},
+    processor::{
+        wait_for_shutdown_signal, ShutdownHandle, TransactionProcessor,
+    },
+    stream::TransactionStream,
+};
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    hash::{Hash, Hasher},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{mpsc, watch};
+
+/// A function that computes the partition key of an event.
+pub type PartitionKeyFn = dyn Fn(&Transaction, &Event) -> String + Send + Sync;
+
+/// Determines which worker handles an event.
+/// Events with the same key are always handled by the same worker, in order.
+#[derive(Clone, Default)]
+pub enum PartitionKey {
+    /// Partition by the address of the emitter of the event.
+    #[default]
+    Emitter,
+    /// Partition by a key computed from the event and its transaction.
+    Custom(Arc<PartitionKeyFn>),
+}
+
+impl PartitionKey {
+    /// Creates a partition key from a function that computes a key for each event.
+    pub fn custom(
+        key: impl Fn(&Transaction, &Event) -> String + Send + Sync + 'static,
+    ) -> Self {
+        Self::Custom(Arc::new(key))
+    }
+
+    /// Returns the index of the worker that should handle the event.
+    fn worker_index(
+        &self,
+        transaction: &Transaction,
+        event: &Event,
+        workers: usize,
+    ) -> usize {
+        let mut hasher = DefaultHasher::new();
+        match self {
+            PartitionKey::Emitter => event.emitter.address().hash(&mut hasher),
+            PartitionKey::Custom(key) => {
+                key(transaction, event).hash(&mut hasher)
+            }
+        }
+        (hasher.finish() % workers as u64) as usize
+    }
+}
+
+/// The events of a transaction that were assigned to a worker.
+struct WorkItem {
+    transaction: Arc<Transaction>,
+    event_indices: Vec<u16>,
+}
+
+/// A message from a worker to the dispatcher.
+enum WorkerMessage {
+    Done(u64),
+    Failed(TransactionProcessorError),
+}
+
+/// Keeps track of the transactions that are still being processed
+/// by one or more workers, to determine the watermark.
+#[derive(Debug, Default)]
+struct Watermark {
+    /// The number of workers still busy with each state version.
+    pending: BTreeMap<u64, usize>,
+    last_dispatched: Option<u64>,
+}
+
+impl Watermark {
+    fn dispatch(&mut self, state_version: u64, workers: usize) {
+        if workers > 0 {
+            self.pending.insert(state_version, workers);
+        }
+        self.last_dispatched = Some(state_version);
+    }
+
+    fn complete(&mut self, state_version: u64) {
+        if let Some(workers) = self.pending.get_mut(&state_version) {
+            *workers -= 1;
+            if *workers == 0 {
+                self.pending.remove(&state_version);
+            }
+        }
+    }
+
+    /// The state version up to which all transactions have been processed.
+    fn committed_state_version(&self) -> Option<u64> {
+        match self.pending.first_key_value() {
+            Some((state_version, _)) => state_version.checked_sub(1),
+            None => self.last_dispatched,
+        }
+    }
+}
+
+/// Processes transactions from a [`TransactionStream`] with multiple workers.
+/// See the [module documentation][crate::concurrent] for how work is divided.
+///
+/// It is created with a factory which builds the [`TransactionProcessor`] for
+/// each worker, so each worker has its own state, handler registry, transaction
+/// handler and logger. Checkpoints are not stored by the workers but by this
+/// processor, so a [`CheckpointStore`] set on a worker's processor is ignored.
+/// Workers handle one transaction at a time, so a
+/// [`BatchTransactionHandler`][crate::transaction_handler::BatchTransactionHandler]
+/// is not used either.
+#[allow(non_camel_case_types)]
+pub struct ConcurrentTransactionStreamProcessor<STREAM, STATE>
+where
+    STREAM: TransactionStream,
+    STATE: State,
+{
+    transaction_stream: STREAM,
+    workers: Vec<TransactionProcessor<STATE>>,
+    partition_key: PartitionKey,
+    worker_buffer_capacity: usize,
+    checkpoint_store: Option<Box<dyn CheckpointStore>>,
+    checkpoint_interval: Duration,
+    watermark_sender: watch::Sender<Option<u64>>,
+    shutdown_sender: Arc<watch::Sender<bool>>,
+    shutdown_on_signals: bool,
+}
+
+#[allow(non_camel_case_types)]
+impl<STREAM, STATE> ConcurrentTransactionStreamProcessor<STREAM, STATE>
+where
+    STREAM: TransactionStream,
+    STATE: State,
+{
+    /// Creates a new processor with `workers` workers. The factory is
+    /// called with the index of each worker to build its processor.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `workers` is 0.
+    pub fn new(
+        transaction_stream: STREAM,
+        workers: usize,
+        mut processor_factory: impl FnMut(usize) -> TransactionProcessor<STATE>,
+    ) -> Self {
+        assert!(workers > 0, "At least one worker is required");
+        let workers = (0..workers)
+            .map(|index| {
+                let mut processor = processor_factory(index);
+                processor.checkpoint_store = None;
+                processor
+            })
+            .collect();
+        Self {
+            transaction_stream,
+            workers,
+            partition_key: PartitionKey::default(),
+            worker_buffer_capacity: 10_000,
+            checkpoint_store: None,
+            checkpoint_interval: Duration::from_secs(5),
+            watermark_sender: watch::channel(None).0,
+            shutdown_sender: Arc::new(watch::channel(false).0),
+            shutdown_on_signals: false,
+        }
+    }
+
+    /// Sets how events are divided over the workers.
+    /// By default, they are partitioned by emitter address.
+    pub fn partition_key(self, partition_key: PartitionKey) -> Self {
+        Self {
+            partition_key,
+            ..self
+        }
+    }
+
+    /// Sets the number of transactions that can be queued for each worker.
+    /// When a worker's queue is full, the processor waits before taking
+    /// more transactions from the stream.
+    pub fn worker_buffer_capacity(self, worker_buffer_capacity: usize) -> Self {
+        Self {
+            worker_buffer_capacity: worker_buffer_capacity.max(1),
+            ..self
+        }
+    }
+
+    /// Sets a [`CheckpointStore`] in which the watermark is stored,
+    /// and from which the stream is resumed on the next run.
+    pub fn checkpoint_store(
+        self,
+        checkpoint_store: impl CheckpointStore + 'static,
+    ) -> Self {
+        Self {
+            checkpoint_store: Some(Box::new(checkpoint_store)),
+            ..self
+        }
+    }
+
+    /// Sets the max time between saving checkpoints while the watermark moves.
+    pub fn checkpoint_interval(self, checkpoint_interval: Duration) -> Self {
+        Self {
+            checkpoint_interval,
+            ..self
+        }
+    }
+
+    /// Stops the processor gracefully when the process receives SIGINT or SIGTERM.
+    pub fn shutdown_on_signals(self) -> Self {
+        Self {
+            shutdown_on_signals: true,
+            ..self
+        }
+    }
+
+    /// Returns a [`ShutdownHandle`] which can be used to stop
+    /// the processor gracefully while it is running. Each worker
+    /// finishes the transaction it is working on before stopping.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle::new(self.shutdown_sender.clone())
+    }
+
+    /// Returns a receiver that is updated with the watermark, which is the
+    /// state version up to which all transactions have been fully processed.
+    pub fn watermark(&self) -> watch::Receiver<Option<u64>> {
+        self.watermark_sender.subscribe()
+    }
+
+    /// The processors of the workers, which hold their state.
+    /// While the processor is running, this is empty.
+    pub fn workers(&self) -> &[TransactionProcessor<STATE>] {
+        &self.workers
+    }
+
+    /// Starts processing transactions from the [`TransactionStream`].
+    ///
+    /// Returns the watermark when the stream is finished or a shutdown
+    /// was requested through a [`ShutdownHandle`].
+    pub async fn run(
+        &mut self,
+    ) -> Result<Option<u64>, TransactionProcessorError> {
+        if let Some(checkpoint_store) = &self.checkpoint_store {
+            if let Some(state_version) = checkpoint_store
+                .resume_state_version()
+                .await
+                .map_err(TransactionProcessorError::UnrecoverableError)?
+            {
+                log::info!(
+                    "Resuming from checkpoint at state version {}",
+                    state_version
+                );
+                self.transaction_stream
+                    .resume_from_state_version(state_version);
+            }
+        }
+        // The stream reports its errors to the logger of the first worker.
+        if let Some(logger) = &self.workers[0].logger {
+            self.transaction_stream.set_logger(logger.clone());
+        }
+        let mut receiver = self
+            .transaction_stream
+            .start()
+            .await
+            .map_err(TransactionProcessorError::UnrecoverableError)?;
+        let signal_joinhandle = if self.shutdown_on_signals {
+            let shutdown_handle = self.shutdown_handle();
+            Some(tokio::spawn(async move {
+                wait_for_shutdown_signal().await;
+                shutdown_handle.shutdown();
+            }))
+        } else {
+            None
+        };
+
+        // The messages channel is unbounded, so workers never wait on
+        // the dispatcher, which may be waiting on a full worker queue.
+        let (message_sender, mut message_receiver) = mpsc::unbounded_channel();
+        let mut work_senders = Vec::with_capacity(self.workers.len());
+        let mut worker_joinhandles = Vec::with_capacity(self.workers.len());
+        for processor in self.workers.drain(..) {
+            let (work_sender, work_receiver) =
+                mpsc::channel(self.worker_buffer_capacity);
+            work_senders.push(work_sender);
+            worker_joinhandles.push(tokio::spawn(run_worker(
+                processor,
+                work_receiver,
+                message_sender.clone(),
+                self.shutdown_sender.subscribe(),
+            )));
+        }
+        drop(message_sender);
+
+        let mut watermark = Watermark::default();
+        let mut last_checkpoint_time = Instant::now();
+        let mut shutdown_receiver = self.shutdown_sender.subscribe();
+        let mut result = Ok(());
+        let mut stream_error = None;
+        loop {
+            let transaction = tokio::select! {
+                biased;
+                _ = shutdown_receiver.wait_for(|shutdown| *shutdown) => {
+                    log::info!("Shutdown requested, stopping the processor");
+                    break;
+                }
+                Some(message) = message_receiver.recv() => {
+                    match message {
+                        WorkerMessage::Done(state_version) => {
+                            watermark.complete(state_version);
+                        }
+                        WorkerMessage::Failed(err) => {
+                            result = Err(err);
+                            break;
+                        }
+                    }
+                    self.update_watermark(&watermark);
+                    if last_checkpoint_time.elapsed() >= self.checkpoint_interval {
+                        if let Err(err) = self.save_checkpoint().await {
+                            result = Err(err);
+                            break;
+                        }
+                        last_checkpoint_time = Instant::now();
+                    }
+                    continue;
+                }
+                transaction = receiver.recv() => match transaction {
+                    Some(transaction) => transaction,
+                    None => {
+                        stream_error = self.transaction_stream.take_error();
+                        break;
+                    }
+                },
+            };
+            // Group the events of the transaction by worker.
+            let mut event_indices = vec![Vec::new(); work_senders.len()];
+            for (index, event) in transaction.events.iter().enumerate() {
+                let worker = self.partition_key.worker_index(
+                    &transaction,
+                    event,
+                    work_senders.len(),
+                );
+                event_indices[worker].push(index as u16);
+            }
+            let workers = event_indices
+                .iter()
+                .filter(|event_indices| !event_indices.is_empty())
+                .count();
+            watermark.dispatch(transaction.state_version, workers);
+            let transaction = Arc::new(transaction);
+            for (work_sender, event_indices) in
+                work_senders.iter().zip(event_indices)
+            {
+                if event_indices.is_empty() {
+                    continue;
+                }
+                let work_item = WorkItem {
+                    transaction: transaction.clone(),
+                    event_indices,
+                };
+                // A worker only stops receiving when it failed,
+                // and the failure is picked up below.
+                if work_sender.send(work_item).await.is_err() {
+                    break;
+                }
+            }
+            self.update_watermark(&watermark);
+        }
+
+        // Let the workers finish what they're doing. On a shutdown or failure,
+        // they stop after their current transaction, otherwise they
+        // finish all transactions in their queue first.
+        if result.is_err() {
+            self.shutdown_handle().shutdown();
+        }
+        drop(work_senders);
+        while let Some(message) = message_receiver.recv().await {
+            match message {
+                WorkerMessage::Done(state_version) => {
+                    watermark.complete(state_version)
+                }
+                WorkerMessage::Failed(err) => {
+                    if result.is_ok() {
+                        result = Err(err);
+                    }
+                }
+            }
+        }
+        self.update_watermark(&watermark);
+        // The transactions the stream delivered before it gave up
+        // are handled, but the processor still fails.
+        if let (Some(err), Ok(())) = (stream_error, &result) {
+            result = Err(TransactionProcessorError::UnrecoverableError(err));
+        }
+        for joinhandle in worker_joinhandles {
+            match joinhandle.await {
+                Ok(processor) => self.workers.push(processor),
+                Err(err) => log::error!("Worker task failed: {:?}", err),
+            }
+        }
+        if let Some(handle) = signal_joinhandle {
+            handle.abort();
+        }
+        self.transaction_stream.stop().await;
+        self.save_checkpoint().await?;
+        result.map(|_| watermark.committed_state_version())
+    }
+
+    fn update_watermark(&self, watermark: &Watermark) {
+        let committed_state_version = watermark.committed_state_version();
+        self.watermark_sender.send_if_modified(|current| {
+            if *current != committed_state_version {
+                *current = committed_state_version;
+                true
+            } else {
+                false
+            }
+        });
+    }
+
+    /// Writes the watermark to the [`CheckpointStore`], if one is set.
+    async fn save_checkpoint(
+        &mut self,
+    ) -> Result<(), TransactionProcessorError> {
+        let committed_state_version = *self.watermark_sender.borrow();
+        if let (Some(checkpoint_store), Some(state_version)) =
+            (&mut self.checkpoint_store, committed_state_version)
+        {
+            checkpoint_store
+                .save(state_version)
+                .await
+                .map_err(TransactionProcessorError::UnrecoverableError)?;
+        }
+        Ok(())
+    }
+}
+
+/// Processes the work items of a single worker, until its queue is closed
+/// or a shutdown is requested. Returns the processor, so its state is kept.
+async fn run_worker<STATE: State>(
+    mut processor: TransactionProcessor<STATE>,
+    mut work_receiver: mpsc::Receiver<WorkItem>,
+    message_sender: mpsc::UnboundedSender<WorkerMessage>,
+    mut shutdown_receiver: watch::Receiver<bool>,
+) -> TransactionProcessor<STATE> {
+    loop {
+        let work_item = tokio::select! {
+            biased;
+            _ = shutdown_receiver.wait_for(|shutdown| *shutdown) => break,
+            work_item = work_receiver.recv() => match work_item {
+                Some(work_item) => work_item,
+                None => break,
+            },
+        };
+        let message = match processor
+            .process_transaction_events(
+                &work_item.transaction,
+                Some(&work_item.event_indices),
+            )
+            .await
+        {
+            Ok(()) => WorkerMessage::Done(work_item.transaction.state_version),
+            Err(err) => WorkerMessage::Failed(err),
+        };
+        let failed = matches!(message, WorkerMessage::Failed(_));
+        let _ = message_sender.send(message);
+        if failed {
+            break;
+        }
+    }
+    processor
+}
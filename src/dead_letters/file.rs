@@ -0,0 +1,94 @@
+//! A dead letter sink that stores dead letters in a newline-delimited JSON file.
+
+use crate::dead_letters::{DeadLetter, DeadLetterSink, ParkedDeadLetter};
+use async_trait::async_trait;
+use std::path::PathBuf;
+use tokio::io::AsyncWriteExt;
+
+/// A [`DeadLetterSink`] that appends each dead letter as a line
+/// of JSON to a file. Removing a dead letter rewrites the file,
+/// which is fine as long as replays are rare compared to parking.
+#[derive(Debug)]
+pub struct FileDeadLetterSink {
+    path: PathBuf,
+    next_id: Option<u64>,
+}
+
+impl FileDeadLetterSink {
+    /// Creates a new sink which reads and writes the given file.
+    /// The file doesn't have to exist yet.
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            next_id: None,
+        }
+    }
+
+    async fn read(&self) -> Result<Vec<ParkedDeadLetter>, anyhow::Error> {
+        let contents = match tokio::fs::read_to_string(&self.path).await {
+            Ok(contents) => contents,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Vec::new())
+            }
+            Err(err) => return Err(err.into()),
+        };
+        contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| {
+                serde_json::from_str::<ParkedDeadLetter>(line)
+                    .map_err(anyhow::Error::from)
+            })
+            .collect()
+    }
+}
+
+#[async_trait]
+impl DeadLetterSink for FileDeadLetterSink {
+    async fn park(
+        &mut self,
+        dead_letter: DeadLetter,
+    ) -> Result<(), anyhow::Error> {
+        let id = match self.next_id {
+            Some(id) => id,
+            None => self
+                .read()
+                .await?
+                .iter()
+                .map(|parked| parked.id + 1)
+                .max()
+                .unwrap_or(0),
+        };
+        let mut line =
+            serde_json::to_string(&ParkedDeadLetter { id, dead_letter })?;
+        line.push('\n');
+        let mut file = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .await?;
+        file.write_all(line.as_bytes()).await?;
+        file.sync_data().await?;
+        self.next_id = Some(id + 1);
+        Ok(())
+    }
+
+    async fn list(&mut self) -> Result<Vec<ParkedDeadLetter>, anyhow::Error> {
+        self.read().await
+    }
+
+    async fn remove(&mut self, id: u64) -> Result<(), anyhow::Error> {
+        let mut contents = String::new();
+        for parked in self.read().await? {
+            if parked.id != id {
+                contents.push_str(&serde_json::to_string(&parked)?);
+                contents.push('\n');
+            }
+        }
+        let mut temporary_path = self.path.clone().into_os_string();
+        temporary_path.push(".tmp");
+        tokio::fs::write(&temporary_path, contents).await?;
+        tokio::fs::rename(&temporary_path, &self.path).await?;
+        Ok(())
+    }
+}
@@ -0,0 +1,96 @@
+//! Sinks that park events which keep failing, so processing can continue.
+//!
+//! When a [`DeadLetterSink`] is set on the processor, an event is parked instead of
+//! stopping or stalling the processor when:
+//! - Its handler keeps returning an `EventRetryError` until the
+//!   [`RetryPolicy`][crate::retry::RetryPolicy] allows no more attempts.
+//! - Its handler returns an `UnrecoverableError`, like when the event can't be decoded.
+//!
+//! Events are parked once the rest of their transaction is handled, so a
+//! transaction that is retried doesn't park the same event more than once.
+//! Changes the handler made to the state before it failed are kept, unless
+//! [`rollback_state_on_retry`][crate::processor::TransactionStreamProcessor::rollback_state_on_retry]
+//! is enabled.
+//!
+//! Parked events can be fed through the handlers again later using
+//! [`TransactionProcessor::replay_dead_letters`][crate::processor::TransactionProcessor::replay_dead_letters].
+//!
+//! A file based sink is always available. The SQLite sink can be
+//! enabled with the `sqlite` feature flag.
+
+use crate::models::{Event, Transaction};
+use async_trait::async_trait;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+
+pub mod file;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+
+/// An event that failed to be handled, together with
+/// everything needed to handle it again later.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DeadLetter {
+    /// The transaction the event is part of.
+    pub transaction: Transaction,
+    /// The event that failed.
+    pub event: Event,
+    /// Zero-based index of the event in the transaction.
+    pub event_index: u16,
+    /// The key of the handler that failed, see
+    /// [`HandlerRegistry::handler_key`][crate::event_handler::HandlerRegistry::handler_key].
+    /// When the event is replayed, only this handler is called again.
+    #[serde(default)]
+    pub handler_key: String,
+    /// The error that was returned last, followed by its causes.
+    pub error_chain: Vec<String>,
+    /// The number of attempts that were made to handle the event.
+    pub attempts: u32,
+    /// When the event was parked.
+    pub parked_at: chrono::DateTime<Utc>,
+}
+
+impl DeadLetter {
+    pub fn new(
+        transaction: &Transaction,
+        event_index: u16,
+        handler_key: &str,
+        error: &anyhow::Error,
+        attempts: u32,
+    ) -> Self {
+        Self {
+            transaction: transaction.clone(),
+            event: transaction.events[event_index as usize].clone(),
+            event_index,
+            handler_key: handler_key.to_string(),
+            error_chain: error.chain().map(|cause| cause.to_string()).collect(),
+            attempts,
+            parked_at: Utc::now(),
+        }
+    }
+}
+
+/// A [`DeadLetter`] as it is stored in a [`DeadLetterSink`],
+/// identified by an id which is assigned by the sink.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ParkedDeadLetter {
+    pub id: u64,
+    pub dead_letter: DeadLetter,
+}
+
+/// A trait that abstracts a persistent store for events that failed to be handled.
+#[async_trait]
+pub trait DeadLetterSink: Send + Sync {
+    /// Stores a dead letter. The processor continues with
+    /// the next event once this call succeeds.
+    async fn park(
+        &mut self,
+        dead_letter: DeadLetter,
+    ) -> Result<(), anyhow::Error>;
+
+    /// Returns all parked dead letters, in the order they were parked.
+    async fn list(&mut self) -> Result<Vec<ParkedDeadLetter>, anyhow::Error>;
+
+    /// Removes a parked dead letter, typically after it was replayed successfully.
+    async fn remove(&mut self, id: u64) -> Result<(), anyhow::Error>;
+}
@@ -0,0 +1,100 @@
+//! A dead letter sink that stores dead letters in a SQLite database.
+
+use crate::dead_letters::{DeadLetter, DeadLetterSink, ParkedDeadLetter};
+use async_trait::async_trait;
+use sqlx::{Pool, Sqlite};
+
+/// A [`DeadLetterSink`] backed by a SQLite table. The dead letter
+/// itself is stored as JSON, next to some columns that make it
+/// easy to inspect the table by hand.
+#[derive(Debug, Clone)]
+pub struct SqliteDeadLetterSink {
+    pool: Pool<Sqlite>,
+}
+
+impl SqliteDeadLetterSink {
+    /// Creates a new sink on an existing connection pool. The
+    /// `event_stream_dead_letters` table is created if it doesn't exist yet.
+    pub async fn new(pool: Pool<Sqlite>) -> Result<Self, anyhow::Error> {
+        sqlx::query(
+            r#"
+                CREATE TABLE IF NOT EXISTS event_stream_dead_letters (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    state_version INTEGER NOT NULL,
+                    event_index INTEGER NOT NULL,
+                    event_name TEXT NOT NULL,
+                    error TEXT NOT NULL,
+                    data TEXT NOT NULL
+                )
+            "#,
+        )
+        .execute(&pool)
+        .await?;
+        Ok(Self { pool })
+    }
+
+    /// Connects to the database at `database_url` and creates a new sink on it.
+    pub async fn connect(database_url: &str) -> Result<Self, anyhow::Error> {
+        let pool = sqlx::sqlite::SqlitePoolOptions::new()
+            .connect(database_url)
+            .await?;
+        Self::new(pool).await
+    }
+}
+
+#[async_trait]
+impl DeadLetterSink for SqliteDeadLetterSink {
+    async fn park(
+        &mut self,
+        dead_letter: DeadLetter,
+    ) -> Result<(), anyhow::Error> {
+        sqlx::query(
+            r#"
+                INSERT INTO event_stream_dead_letters
+                    (state_version, event_index, event_name, error, data)
+                VALUES ($1, $2, $3, $4, $5)
+            "#,
+        )
+        .bind(dead_letter.transaction.state_version as i64)
+        .bind(dead_letter.event_index as i64)
+        .bind(&dead_letter.event.name)
+        .bind(dead_letter.error_chain.join(": "))
+        .bind(serde_json::to_string(&dead_letter)?)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list(&mut self) -> Result<Vec<ParkedDeadLetter>, anyhow::Error> {
+        let rows: Vec<(i64, String)> = sqlx::query_as(
+            r#"
+                SELECT id, data
+                FROM event_stream_dead_letters
+                ORDER BY id ASC
+            "#,
+        )
+        .fetch_all(&self.pool)
+        .await?;
+        rows.into_iter()
+            .map(|(id, data)| {
+                Ok(ParkedDeadLetter {
+                    id: id as u64,
+                    dead_letter: serde_json::from_str(&data)?,
+                })
+            })
+            .collect()
+    }
+
+    async fn remove(&mut self, id: u64) -> Result<(), anyhow::Error> {
+        sqlx::query(
+            r#"
+                DELETE FROM event_stream_dead_letters
+                WHERE id = $1
+            "#,
+        )
+        .bind(id as i64)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
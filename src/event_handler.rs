@@ -57,6 +57,7 @@ async fn event_handler_name(
 use async_trait::async_trait;
 use dyn_clone::DynClone;
 use radix_client::gateway::models::{EntityType, ModuleId};
+use serde::{Deserialize, Serialize};
 use std::{
     any::{Any, TypeId},
     collections::HashMap,
@@ -64,8 +65,10 @@ use std::{
 
 use crate::{
     error::EventHandlerError,
+    event_matcher::{EventMatcher, MatcherTable},
     models::{Event, EventEmitter, Transaction},
     native_events::NativeEventType,
+    retry::RetryPolicy,
 };
 
 /// A shorthand trait for a state type that can be used in event handlers.
@@ -81,10 +84,25 @@ impl<T> State for T where T: Send + Sync + 'static {}
 /// that allows some other types to be a bit simpler.
 /// It can only contain event handlers of one specific type, which is
 /// implicitly determined by the first handler that is added to the registry.
+///
+/// Handlers are registered for an [`EventMatcher`] or a [`NativeEventType`].
+/// Multiple handlers can be registered for the same matcher, in which case
+/// they are all called, in the order they were added. See
+/// [`crate::event_matcher`] for how the handlers for an event are resolved.
+///
+/// Besides its [`HandlerId`], each handler has a key that identifies it
+/// across restarts, for example in [dead letters][crate::dead_letters::DeadLetter].
+/// By default, the key is made up of the matcher and the position of the
+/// handler among the handlers that were added for it, like `Name("SwapEvent")#0`.
+/// A handler can be given a name instead, using [`HandlerRegistry::set_handler_name`].
+///
+/// Retry policies and whether failed transactions are handled are set
+/// per matcher, and apply to all handlers of that matcher.
 #[derive(Default)]
 pub struct HandlerRegistry {
-    handlers: HashMap<(String, String), Box<dyn Any + Send + Sync>>,
-    native_handlers: HashMap<NativeEventType, Box<dyn Any + Send + Sync>>,
+    handlers: MatcherTable<HandlerGroup>,
+    native_handlers: HashMap<NativeEventType, HandlerGroup>,
+    next_handler_id: u64,
     type_id: Option<TypeId>,
 }
 
@@ -94,54 +112,55 @@ impl HandlerRegistry {
         Self::default()
     }
 
+    /// Returns whether a handler is registered for the event.
+    /// This is called for every event, so it only does a few hash lookups.
     pub fn handler_exists(&self, event: &Event) -> bool {
-        let native_event_case =
-            |entity_type: EntityType| match NativeEventType::resolve(
-                &event.name,
-                entity_type,
-            ) {
-                Ok(event_type) => {
-                    self.native_handlers.contains_key(&event_type)
-                }
-                Err(_) => false,
-            };
-        let userspace_event_case = |entity_address: &str| {
-            self.handlers.contains_key(&(
-                entity_address.to_string(),
-                event.name.to_string(),
-            ))
-        };
-        match &event.emitter {
-            EventEmitter::Method {
-                entity_address,
-                entity_type,
-                object_module_id,
-                ..
-            } => {
-                if !matches!(object_module_id, ModuleId::Main) {
-                    native_event_case(entity_type.clone())
-                } else {
-                    match entity_type {
-                        EntityType::GlobalGenericComponent => {
-                            userspace_event_case(entity_address)
-                        }
-                        EntityType::InternalGenericComponent => {
-                            userspace_event_case(entity_address)
-                        }
-                        _ => native_event_case(entity_type.clone()),
-                    }
-                }
-            }
-            EventEmitter::Function {
-                package_address, ..
-            } => userspace_event_case(package_address),
+        self.resolve_group(event).is_some()
+    }
+
+    /// Returns whether the event should be handled as part of the given
+    /// transaction. Besides a handler being registered, this requires the
+    /// transaction to be successful, unless the handler opted in to
+    /// events from failed transactions.
+    pub fn handles_event(
+        &self,
+        transaction: &Transaction,
+        event: &Event,
+    ) -> bool {
+        self.resolve_group(event).is_some_and(|group| {
+            transaction.status.is_success() || group.handles_failed_transactions
+        })
+    }
+
+    /// Returns the sorted addresses of the emitters that handlers are
+    /// registered for, or `None` if there are handlers that match events
+    /// from any emitter, like handlers for native events or blueprints.
+    pub fn emitter_addresses(&self) -> Option<Vec<String>> {
+        if self
+            .native_handlers
+            .values()
+            .any(HandlerGroup::has_handlers)
+        {
+            return None;
         }
+        let mut addresses: Vec<String> = self
+            .handlers
+            .emitter_addresses()?
+            .into_iter()
+            .map(str::to_string)
+            .collect();
+        addresses.sort();
+        addresses.dedup();
+        Some(addresses)
     }
 
-    /// Add an event handler to the registry.
+    /// Add an event handler to the registry for events with the
+    /// given name from the given emitter address.
     /// It is only possible to add handlers with the same signature.
     /// The signature is determined by the first handler that is added to the registry.
     ///
+    /// Returns an id which can be used to remove the handler again.
+    ///
     /// # Panics
     ///
     /// Panics if the added handler has a different signature than the
@@ -151,31 +170,86 @@ impl HandlerRegistry {
         emitter: &str,
         name: &str,
         handler: impl EventHandler<STATE, TRANSACTION_CONTEXT> + 'static,
-    ) {
-        // Get the type ID of the handler
-        let type_id =
-            TypeId::of::<Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT>>>();
-        match self.type_id {
-            // If there is already a type ID, we check if it matches the handler
-            // we're trying to add.
-            Some(existing_type_id) => {
-                if existing_type_id != type_id {
-                    panic!("HandlerRegistry already contains a handler with a different signature");
-                }
-            }
-            // If there is no type ID yet, we implicitly set it here.
-            None => {
-                self.type_id = Some(type_id);
+    ) -> HandlerId {
+        self.add_handler_matching(EventMatcher::exact(emitter, name), handler)
+    }
+
+    /// Add an event handler to the registry for all events matched by
+    /// the [`EventMatcher`]. If handlers were added before with the same
+    /// matcher, the new handler is called after them.
+    ///
+    /// Returns an id which can be used to remove the handler again.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the added handler has a different signature than the
+    /// handlers already in the registry.
+    pub fn add_handler_matching<STATE: State, TRANSACTION_CONTEXT: 'static>(
+        &mut self,
+        matcher: EventMatcher,
+        handler: impl EventHandler<STATE, TRANSACTION_CONTEXT> + 'static,
+    ) -> HandlerId {
+        let position = self
+            .handlers
+            .get(&matcher)
+            .map_or(0, |group| group.handlers.len());
+        let registered =
+            self.register(format!("{:?}#{}", matcher, position), handler);
+        let id = registered.id;
+        self.handlers
+            .get_or_insert_default(matcher)
+            .handlers
+            .push(registered);
+        id
+    }
+
+    /// Removes a handler that was added before.
+    /// Returns whether the handler was still in the registry.
+    pub fn remove_handler(&mut self, id: HandlerId) -> bool {
+        let mut removed = false;
+        let mut keep_others = |group: &mut HandlerGroup| {
+            group.handlers.retain(|handler| {
+                removed |= handler.id == id;
+                handler.id != id
+            });
+            !group.is_empty()
+        };
+        self.handlers.retain(&mut keep_others);
+        self.native_handlers.retain(|_, group| keep_others(group));
+        removed
+    }
+
+    /// Gives a handler a name, which is used as its key instead of its
+    /// matcher and position. The name should be unique, and the same
+    /// every time the handler is registered, including after a restart.
+    /// Returns whether the handler is in the registry.
+    pub fn set_handler_name(&mut self, id: HandlerId, name: &str) -> bool {
+        let mut found = false;
+        let mut rename = |group: &mut HandlerGroup| {
+            for handler in
+                group.handlers.iter_mut().filter(|handler| handler.id == id)
+            {
+                handler.key = name.to_string();
+                found = true;
             }
-        }
-        // Box the handler and insert it into the registry.
-        let boxed: Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT> + 'static> =
-            Box::new(handler);
+            true
+        };
+        self.handlers.retain(&mut rename);
+        self.native_handlers.retain(|_, group| rename(group));
+        found
+    }
+
+    /// Returns the key of a handler, which identifies it across restarts.
+    pub fn handler_key(&self, id: HandlerId) -> Option<&str> {
         self.handlers
-            .insert((emitter.to_string(), name.to_string()), Box::new(boxed));
+            .values()
+            .chain(self.native_handlers.values())
+            .flat_map(|group| &group.handlers)
+            .find(|registered| registered.id == id)
+            .map(|registered| registered.key.as_str())
     }
 
-    /// Get an event handler from the registry.
+    /// Get the first event handler registered for the given emitter and event name.
     /// The handler is downcast to the correct type.
     ///
     /// # Panics
@@ -188,52 +262,342 @@ impl HandlerRegistry {
         emitter: &str,
         name: &str,
     ) -> Option<&Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT>>> {
+        self.handlers_matching(&EventMatcher::exact(emitter, name))
+            .into_iter()
+            .next()
+    }
+
+    /// Get the event handlers registered for exactly this
+    /// [`EventMatcher`], in the order they were added.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the type parameters used to call it
+    /// don't match the ones used to add the handler to the registry.
+    #[allow(clippy::borrowed_box)]
+    pub fn handlers_matching<STATE: State, TRANSACTION_CONTEXT: 'static>(
+        &self,
+        matcher: &EventMatcher,
+    ) -> Vec<&Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT>>> {
         self.validate_type_id::<STATE, TRANSACTION_CONTEXT>();
+        self.handlers
+            .get(matcher)
+            .into_iter()
+            .flat_map(|group| &group.handlers)
+            .map(Self::downcast)
+            .collect()
+    }
 
-        // Get the handler from the registry and downcast it to the correct type.
-        let handler =
-            self.handlers.get(&(emitter.to_string(), name.to_string()));
-        handler.map(|handler| {
-            handler
-                .downcast_ref::<Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT>>>()
-                .expect("Failed to downcast handler")
-        })
+    /// Removes all event handlers registered for this [`EventMatcher`].
+    /// The retry policy and other settings of the matcher are kept.
+    /// Returns whether any handler was removed.
+    pub fn remove_handler_matching(&mut self, matcher: &EventMatcher) -> bool {
+        let Some(mut group) = self.handlers.remove(matcher) else {
+            return false;
+        };
+        let removed = group.has_handlers();
+        group.handlers.clear();
+        if !group.is_empty() {
+            self.handlers.insert(matcher.clone(), group);
+        }
+        removed
     }
 
+    /// Get the handlers that should handle the event, in the order they
+    /// were added. These are the handlers of its native event type,
+    /// or the handlers of the most specific matcher.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the type parameters used to call it
+    /// don't match the ones used to add the handler to the registry.
+    #[allow(clippy::borrowed_box, clippy::type_complexity)]
+    pub fn resolve_handlers<STATE: State, TRANSACTION_CONTEXT: 'static>(
+        &self,
+        event: &Event,
+    ) -> Vec<(
+        HandlerId,
+        &Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT>>,
+    )> {
+        self.validate_type_id::<STATE, TRANSACTION_CONTEXT>();
+        self.resolve_registered(event)
+            .map(|registered| (registered.id, Self::downcast(registered)))
+            .collect()
+    }
+
+    /// Get the handler with the given key, if it is one
+    /// of the handlers that should handle the event.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the type parameters used to call it
+    /// don't match the ones used to add the handler to the registry.
+    #[allow(clippy::borrowed_box)]
+    pub fn resolve_handler_by_key<
+        STATE: State,
+        TRANSACTION_CONTEXT: 'static,
+    >(
+        &self,
+        event: &Event,
+        key: &str,
+    ) -> Option<&Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT>>> {
+        self.validate_type_id::<STATE, TRANSACTION_CONTEXT>();
+        self.resolve_registered(event)
+            .find(|registered| registered.key == key)
+            .map(Self::downcast)
+    }
+
+    fn resolve_registered(
+        &self,
+        event: &Event,
+    ) -> impl Iterator<Item = &RegisteredHandler> {
+        self.resolve_group(event)
+            .into_iter()
+            .flat_map(|group| &group.handlers)
+    }
+
+    /// Returns the group of the most specific matcher or native event type
+    /// that has handlers for the event. Its handlers, retry policy and
+    /// whether it handles failed transactions all come from this group.
+    /// Native handlers come first, so registering a matcher for the address
+    /// of a vault or resource doesn't take over its native events.
+    fn resolve_group(&self, event: &Event) -> Option<&HandlerGroup> {
+        Self::native_event_type(event)
+            .and_then(|event_type| self.native_handlers.get(&event_type))
+            .filter(|group| group.has_handlers())
+            .or_else(|| {
+                self.handlers
+                    .resolve_exact(event)
+                    .filter(|group| group.has_handlers())
+            })
+            .or_else(|| {
+                self.handlers
+                    .resolve_wildcard(event, HandlerGroup::has_handlers)
+            })
+    }
+
+    /// Sets the handler for a native event type,
+    /// replacing any handlers that were added before.
+    ///
+    /// Returns an id which can be used to remove the handler again.
     pub fn set_native_handler<STATE: State, TRANSACTION_CONTEXT: 'static>(
         &mut self,
         event_type: NativeEventType,
         handler: impl EventHandler<STATE, TRANSACTION_CONTEXT> + 'static,
+    ) -> HandlerId {
+        let registered = self.register(format!("{:?}#0", event_type), handler);
+        let id = registered.id;
+        self.native_handlers.entry(event_type).or_default().handlers =
+            vec![registered];
+        id
+    }
+
+    /// Adds a handler for a native event type. If handlers were added
+    /// before for the same type, the new handler is called after them.
+    ///
+    /// Returns an id which can be used to remove the handler again.
+    pub fn add_native_handler<STATE: State, TRANSACTION_CONTEXT: 'static>(
+        &mut self,
+        event_type: NativeEventType,
+        handler: impl EventHandler<STATE, TRANSACTION_CONTEXT> + 'static,
+    ) -> HandlerId {
+        let position = self
+            .native_handlers
+            .get(&event_type)
+            .map_or(0, |group| group.handlers.len());
+        let registered =
+            self.register(format!("{:?}#{}", event_type, position), handler);
+        let id = registered.id;
+        self.native_handlers
+            .entry(event_type)
+            .or_default()
+            .handlers
+            .push(registered);
+        id
+    }
+
+    /// Get the first handler for a native event type.
+    #[allow(clippy::borrowed_box)]
+    pub fn native_handler<STATE: State, TRANSACTION_CONTEXT: 'static>(
+        &self,
+        event_type: NativeEventType,
+    ) -> Option<&Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT>>> {
+        self.validate_type_id::<STATE, TRANSACTION_CONTEXT>();
+        self.native_handlers
+            .get(&event_type)
+            .and_then(|group| group.handlers.first())
+            .map(Self::downcast)
+    }
+
+    /// Sets the [`RetryPolicy`] for `EventRetryError`s returned by the handlers
+    /// for the given emitter and event name. It overrides the event retry
+    /// policy of the processor for all handlers of this matcher.
+    pub fn set_retry_policy(
+        &mut self,
+        emitter: &str,
+        name: &str,
+        retry_policy: RetryPolicy,
     ) {
+        self.set_retry_policy_matching(
+            EventMatcher::exact(emitter, name),
+            retry_policy,
+        );
+    }
+
+    /// Sets the [`RetryPolicy`] for `EventRetryError`s returned while handling
+    /// events matched by the [`EventMatcher`]. It overrides the event retry
+    /// policy of the processor for these events.
+    pub fn set_retry_policy_matching(
+        &mut self,
+        matcher: EventMatcher,
+        retry_policy: RetryPolicy,
+    ) {
+        self.handlers.get_or_insert_default(matcher).retry_policy =
+            Some(retry_policy);
+    }
+
+    /// Sets the [`RetryPolicy`] for `EventRetryError`s returned by the handlers
+    /// for a native event type. It overrides the event retry
+    /// policy of the processor for all handlers of this type.
+    pub fn set_native_retry_policy(
+        &mut self,
+        event_type: NativeEventType,
+        retry_policy: RetryPolicy,
+    ) {
+        self.native_handlers
+            .entry(event_type)
+            .or_default()
+            .retry_policy = Some(retry_policy);
+    }
+
+    /// Sets whether the handlers for the given emitter and event name
+    /// receive events from failed transactions. By default, handlers
+    /// only receive events from successful transactions.
+    ///
+    /// Note that failed transactions are only streamed
+    /// when the source is configured to include them.
+    pub fn set_handles_failed_transactions(
+        &mut self,
+        emitter: &str,
+        name: &str,
+        handles_failed_transactions: bool,
+    ) {
+        self.set_handles_failed_transactions_matching(
+            EventMatcher::exact(emitter, name),
+            handles_failed_transactions,
+        );
+    }
+
+    /// Sets whether events matched by the [`EventMatcher`]
+    /// are handled when they are part of a failed transaction.
+    pub fn set_handles_failed_transactions_matching(
+        &mut self,
+        matcher: EventMatcher,
+        handles_failed_transactions: bool,
+    ) {
+        self.handlers
+            .get_or_insert_default(matcher)
+            .handles_failed_transactions = handles_failed_transactions;
+    }
+
+    /// Sets whether the handlers for a native event type
+    /// receive events from failed transactions, like fee events.
+    pub fn set_native_handles_failed_transactions(
+        &mut self,
+        event_type: NativeEventType,
+        handles_failed_transactions: bool,
+    ) {
+        self.native_handlers
+            .entry(event_type)
+            .or_default()
+            .handles_failed_transactions = handles_failed_transactions;
+    }
+
+    /// Returns whether the handlers of an event receive
+    /// events from failed transactions.
+    pub fn handles_failed_transactions(&self, event: &Event) -> bool {
+        self.resolve_group(event)
+            .is_some_and(|group| group.handles_failed_transactions)
+    }
+
+    /// Get the [`RetryPolicy`] set for the handlers of an event, if any.
+    /// The policy is the one set for the same matcher or native event type
+    /// that the handlers are resolved from.
+    pub fn retry_policy(&self, event: &Event) -> Option<&RetryPolicy> {
+        self.resolve_group(event)?.retry_policy.as_ref()
+    }
+
+    /// Returns the native event type of an event, if it is a native event.
+    /// Events emitted by the main module of generic components
+    /// and by blueprint functions are never native.
+    fn native_event_type(event: &Event) -> Option<NativeEventType> {
+        match &event.emitter {
+            EventEmitter::Method {
+                entity_type,
+                object_module_id,
+                ..
+            } => {
+                if matches!(object_module_id, ModuleId::Main)
+                    && matches!(
+                        entity_type,
+                        EntityType::GlobalGenericComponent
+                            | EntityType::InternalGenericComponent
+                    )
+                {
+                    return None;
+                }
+                NativeEventType::resolve(&event.name, entity_type.clone()).ok()
+            }
+            EventEmitter::Function { .. } => None,
+        }
+    }
+
+    /// Boxes the handler and assigns it an id,
+    /// so it's ready to be inserted into the registry.
+    fn register<STATE: State, TRANSACTION_CONTEXT: 'static>(
+        &mut self,
+        key: String,
+        handler: impl EventHandler<STATE, TRANSACTION_CONTEXT> + 'static,
+    ) -> RegisteredHandler {
+        self.set_type_id::<STATE, TRANSACTION_CONTEXT>();
+        let boxed: Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT> + 'static> =
+            Box::new(handler);
+        let id = HandlerId(self.next_handler_id);
+        self.next_handler_id += 1;
+        RegisteredHandler {
+            id,
+            key,
+            handler: Box::new(boxed),
+        }
+    }
+
+    #[allow(clippy::borrowed_box)]
+    fn downcast<STATE: State, TRANSACTION_CONTEXT: 'static>(
+        registered: &RegisteredHandler,
+    ) -> &Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT>> {
+        registered
+            .handler
+            .downcast_ref::<Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT>>>()
+            .expect("Failed to downcast handler")
+    }
+
+    fn set_type_id<STATE: State, TRANSACTION_CONTEXT: 'static>(&mut self) {
+        // Get the type ID of the handler
         let type_id =
             TypeId::of::<Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT>>>();
         match self.type_id {
+            // If there is already a type ID, we check if it matches the handler
+            // we're trying to add.
             Some(existing_type_id) => {
                 if existing_type_id != type_id {
                     panic!("HandlerRegistry already contains a handler with a different signature");
                 }
             }
+            // If there is no type ID yet, we implicitly set it here.
             None => {
                 self.type_id = Some(type_id);
             }
         }
-        let boxed: Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT> + 'static> =
-            Box::new(handler);
-        self.native_handlers.insert(event_type, Box::new(boxed));
-    }
-
-    #[allow(clippy::borrowed_box)]
-    pub fn native_handler<STATE: State, TRANSACTION_CONTEXT: 'static>(
-        &self,
-        event_type: NativeEventType,
-    ) -> Option<&Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT>>> {
-        self.validate_type_id::<STATE, TRANSACTION_CONTEXT>();
-        let handler = self.native_handlers.get(&event_type);
-        handler.map(|handler| {
-            handler
-                .downcast_ref::<Box<dyn EventHandler<STATE, TRANSACTION_CONTEXT>>>()
-                .expect("Failed to downcast handler")
-        })
     }
 
     fn validate_type_id<STATE: State, TRANSACTION_CONTEXT: 'static>(&self) {
@@ -249,6 +613,54 @@ impl HandlerRegistry {
     }
 }
 
+/// Identifies a handler in the [`HandlerRegistry`]. Ids are assigned
+/// in the order handlers are added, so they only identify a handler
+/// within one registry. Use [`HandlerRegistry::handler_key`] to identify
+/// a handler across restarts.
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Hash,
+    PartialOrd,
+    Ord,
+    Serialize,
+    Deserialize,
+)]
+pub struct HandlerId(u64);
+
+/// The handlers registered for one matcher or native event type,
+/// together with the settings that apply to all of them.
+#[derive(Default)]
+struct HandlerGroup {
+    handlers: Vec<RegisteredHandler>,
+    retry_policy: Option<RetryPolicy>,
+    handles_failed_transactions: bool,
+}
+
+impl HandlerGroup {
+    fn has_handlers(&self) -> bool {
+        !self.handlers.is_empty()
+    }
+
+    /// Returns whether the group has neither handlers nor settings,
+    /// so it can be removed.
+    fn is_empty(&self) -> bool {
+        self.handlers.is_empty()
+            && self.retry_policy.is_none()
+            && !self.handles_failed_transactions
+    }
+}
+
+/// A type-erased handler together with its id and key.
+struct RegisteredHandler {
+    id: HandlerId,
+    key: String,
+    handler: Box<dyn Any + Send + Sync>,
+}
+
 /// A trait that abstracts an event handler.
 #[allow(non_camel_case_types)]
 #[async_trait]
@@ -0,0 +1,305 @@
+//! Matchers that determine which events a handler is registered for.
+//!
+//! Besides an exact `(emitter address, event name)` pair, handlers can be
+//! registered for an event name from any emitter, for any event from an
+//! emitter, for an event from any component of a blueprint, or for all events.
+//! When multiple matchers match an event, the most specific one wins:
+//!
+//! 1. Native event types, see [`NativeEventType`][crate::native_events::NativeEventType]
+//! 2. [`EventMatcher::Exact`]
+//! 3. [`EventMatcher::Blueprint`]
+//! 4. [`EventMatcher::Name`]
+//! 5. [`EventMatcher::Emitter`]
+//! 6. [`EventMatcher::Any`]
+
+use crate::models::Event;
+use std::collections::HashMap;
+
+/// Describes the events a handler is registered for.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum EventMatcher {
+    /// Events with this name from this emitter address.
+    Exact { emitter: String, name: String },
+    /// Events with this name, emitted by a component of this blueprint
+    /// or by one of its functions.
+    Blueprint {
+        package_address: String,
+        blueprint_name: String,
+        name: String,
+    },
+    /// Events with this name, from any emitter.
+    Name(String),
+    /// Any event from this emitter address.
+    Emitter(String),
+    /// Any event.
+    Any,
+}
+
+impl EventMatcher {
+    pub fn exact(emitter: &str, name: &str) -> Self {
+        Self::Exact {
+            emitter: emitter.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    pub fn blueprint(
+        package_address: &str,
+        blueprint_name: &str,
+        name: &str,
+    ) -> Self {
+        Self::Blueprint {
+            package_address: package_address.to_string(),
+            blueprint_name: blueprint_name.to_string(),
+            name: name.to_string(),
+        }
+    }
+
+    pub fn name(name: &str) -> Self {
+        Self::Name(name.to_string())
+    }
+
+    pub fn emitter(emitter: &str) -> Self {
+        Self::Emitter(emitter.to_string())
+    }
+
+    pub fn any() -> Self {
+        Self::Any
+    }
+
+    /// Returns whether the event is matched.
+    pub fn matches(&self, event: &Event) -> bool {
+        match self {
+            EventMatcher::Exact { emitter, name } => {
+                event.emitter.address() == emitter && &event.name == name
+            }
+            EventMatcher::Blueprint {
+                package_address,
+                blueprint_name,
+                name,
+            } => {
+                &event.name == name
+                    && event.emitter.blueprint()
+                        == Some((package_address, blueprint_name))
+            }
+            EventMatcher::Name(name) => &event.name == name,
+            EventMatcher::Emitter(emitter) => {
+                event.emitter.address() == emitter
+            }
+            EventMatcher::Any => true,
+        }
+    }
+}
+
+/// Values keyed by [`EventMatcher`], stored such that the value for an
+/// event can be resolved with a few hash lookups and without allocating.
+#[derive(Debug)]
+pub(crate) struct MatcherTable<T> {
+    exact: HashMap<String, HashMap<String, T>>,
+    blueprint: HashMap<String, HashMap<String, HashMap<String, T>>>,
+    name: HashMap<String, T>,
+    emitter: HashMap<String, T>,
+    any: Option<T>,
+}
+
+impl<T> Default for MatcherTable<T> {
+    fn default() -> Self {
+        Self {
+            exact: HashMap::new(),
+            blueprint: HashMap::new(),
+            name: HashMap::new(),
+            emitter: HashMap::new(),
+            any: None,
+        }
+    }
+}
+
+impl<T> MatcherTable<T> {
+    /// Inserts a value, replacing and returning the previous one.
+    pub fn insert(&mut self, matcher: EventMatcher, value: T) -> Option<T> {
+        match matcher {
+            EventMatcher::Exact { emitter, name } => {
+                self.exact.entry(emitter).or_default().insert(name, value)
+            }
+            EventMatcher::Blueprint {
+                package_address,
+                blueprint_name,
+                name,
+            } => self
+                .blueprint
+                .entry(package_address)
+                .or_default()
+                .entry(blueprint_name)
+                .or_default()
+                .insert(name, value),
+            EventMatcher::Name(name) => self.name.insert(name, value),
+            EventMatcher::Emitter(emitter) => {
+                self.emitter.insert(emitter, value)
+            }
+            EventMatcher::Any => self.any.replace(value),
+        }
+    }
+
+    /// Returns the value for the matcher, inserting the default value first
+    /// if there is none.
+    pub fn get_or_insert_default(&mut self, matcher: EventMatcher) -> &mut T
+    where
+        T: Default,
+    {
+        match matcher {
+            EventMatcher::Exact { emitter, name } => self
+                .exact
+                .entry(emitter)
+                .or_default()
+                .entry(name)
+                .or_default(),
+            EventMatcher::Blueprint {
+                package_address,
+                blueprint_name,
+                name,
+            } => self
+                .blueprint
+                .entry(package_address)
+                .or_default()
+                .entry(blueprint_name)
+                .or_default()
+                .entry(name)
+                .or_default(),
+            EventMatcher::Name(name) => self.name.entry(name).or_default(),
+            EventMatcher::Emitter(emitter) => {
+                self.emitter.entry(emitter).or_default()
+            }
+            EventMatcher::Any => self.any.get_or_insert_with(T::default),
+        }
+    }
+
+    pub fn get(&self, matcher: &EventMatcher) -> Option<&T> {
+        match matcher {
+            EventMatcher::Exact { emitter, name } => {
+                self.exact.get(emitter)?.get(name)
+            }
+            EventMatcher::Blueprint {
+                package_address,
+                blueprint_name,
+                name,
+            } => self
+                .blueprint
+                .get(package_address)?
+                .get(blueprint_name)?
+                .get(name),
+            EventMatcher::Name(name) => self.name.get(name),
+            EventMatcher::Emitter(emitter) => self.emitter.get(emitter),
+            EventMatcher::Any => self.any.as_ref(),
+        }
+    }
+
+    pub fn remove(&mut self, matcher: &EventMatcher) -> Option<T> {
+        match matcher {
+            EventMatcher::Exact { emitter, name } => {
+                self.exact.get_mut(emitter)?.remove(name)
+            }
+            EventMatcher::Blueprint {
+                package_address,
+                blueprint_name,
+                name,
+            } => self
+                .blueprint
+                .get_mut(package_address)?
+                .get_mut(blueprint_name)?
+                .remove(name),
+            EventMatcher::Name(name) => self.name.remove(name),
+            EventMatcher::Emitter(emitter) => self.emitter.remove(emitter),
+            EventMatcher::Any => self.any.take(),
+        }
+    }
+
+    /// Keeps only the values for which `keep` returns true.
+    /// `keep` may modify the values it is called with.
+    pub fn retain(&mut self, mut keep: impl FnMut(&mut T) -> bool) {
+        for by_name in self.exact.values_mut() {
+            by_name.retain(|_, value| keep(value));
+        }
+        self.exact.retain(|_, by_name| !by_name.is_empty());
+        for by_blueprint in self.blueprint.values_mut() {
+            for by_name in by_blueprint.values_mut() {
+                by_name.retain(|_, value| keep(value));
+            }
+            by_blueprint.retain(|_, by_name| !by_name.is_empty());
+        }
+        self.blueprint
+            .retain(|_, by_blueprint| !by_blueprint.is_empty());
+        self.name.retain(|_, value| keep(value));
+        self.emitter.retain(|_, value| keep(value));
+        if self.any.as_mut().is_some_and(|value| !keep(value)) {
+            self.any = None;
+        }
+    }
+
+    /// Returns all values, in no particular order.
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.exact
+            .values()
+            .flat_map(HashMap::values)
+            .chain(
+                self.blueprint
+                    .values()
+                    .flat_map(HashMap::values)
+                    .flat_map(HashMap::values),
+            )
+            .chain(self.name.values())
+            .chain(self.emitter.values())
+            .chain(self.any.as_ref())
+    }
+
+    /// Returns the emitter addresses of the exact and emitter matchers,
+    /// or `None` if there are matchers that match any emitter.
+    pub fn emitter_addresses(&self) -> Option<Vec<&str>> {
+        if !self.blueprint.is_empty()
+            || !self.name.is_empty()
+            || self.any.is_some()
+        {
+            return None;
+        }
+        Some(
+            self.exact
+                .keys()
+                .chain(self.emitter.keys())
+                .map(String::as_str)
+                .collect(),
+        )
+    }
+
+    /// Returns the value for an exact match of the event.
+    pub fn resolve_exact(&self, event: &Event) -> Option<&T> {
+        self.exact.get(event.emitter.address())?.get(&event.name)
+    }
+
+    /// Returns the value of the most specific wildcard matcher which
+    /// matches the event, so anything but an exact match,
+    /// skipping the values that aren't accepted.
+    pub fn resolve_wildcard(
+        &self,
+        event: &Event,
+        accept: impl Fn(&T) -> bool,
+    ) -> Option<&T> {
+        event
+            .emitter
+            .blueprint()
+            .and_then(|(package_address, blueprint_name)| {
+                self.blueprint
+                    .get(package_address)?
+                    .get(blueprint_name)?
+                    .get(&event.name)
+            })
+            .filter(|value| accept(value))
+            .or_else(|| {
+                self.name.get(&event.name).filter(|value| accept(value))
+            })
+            .or_else(|| {
+                self.emitter
+                    .get(event.emitter.address())
+                    .filter(|value| accept(value))
+            })
+            .or_else(|| self.any.as_ref().filter(|value| accept(value)))
+    }
+}
@@ -1,12 +1,18 @@
+pub mod checkpoints;
+pub mod combinators;
+pub mod concurrent;
+pub mod dead_letters;
 pub mod encode_string_representation;
 pub mod encodings;
 pub mod error;
 pub mod event_handler;
+pub mod event_matcher;
 pub mod logger;
 pub mod macros;
 pub mod models;
 pub mod native_events;
 pub mod processor;
+pub mod retry;
 pub mod sources;
 pub mod stream;
 pub mod transaction_handler;
@@ -6,11 +6,15 @@ at various points in the processing of transactions. This allows for custom logg
 and metric collection. The default implementation is [`DefaultLogger`].
 */
 
-use crate::models::{Event, Transaction};
+use crate::{
+    event_handler::HandlerId,
+    models::{Event, Transaction},
+    stream::StreamErrorKind,
+};
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use colored::Colorize;
-use log::{error, info};
+use log::{error, info, warn};
 use std::{
     collections::VecDeque,
     time::{Duration, Instant},
@@ -114,6 +118,27 @@ pub trait Logger: Send + Sync {
         event: &Event,
         handling: bool,
     );
+    /// Called when one of the handlers of an event is about to be called.
+    /// An event can have multiple handlers, which are called one by one
+    /// in the order they were added, between `receive_event` and `finish_event`.
+    ///
+    /// `is_retry` indicates whether the handler is currently being retried or not.
+    async fn receive_event_handler(
+        &mut self,
+        _transaction: &Transaction,
+        _event: &Event,
+        _handler_id: HandlerId,
+        _is_retry: bool,
+    ) {
+    }
+    /// Called when one of the handlers of an event has handled it successfully.
+    async fn finish_event_handler(
+        &mut self,
+        _transaction: &Transaction,
+        _event: &Event,
+        _handler_id: HandlerId,
+    ) {
+    }
     /// Called when an `EventRetryError` is returned from a handler
     /// and the event is being retried. It could be called multiple times
     /// for the same event if it continues to fail.
@@ -133,9 +158,65 @@ pub trait Logger: Send + Sync {
         error: &anyhow::Error,
         timeout: Duration,
     );
+    /// Called when an event keeps returning an `EventRetryError` and its
+    /// [`RetryPolicy`][crate::retry::RetryPolicy] allows no more attempts.
+    /// After this, the event is parked if a [`DeadLetterSink`][crate::dead_letters::DeadLetterSink]
+    /// is set, or the error is escalated to a transaction retry otherwise.
+    async fn event_retries_exhausted(
+        &mut self,
+        _transaction: &Transaction,
+        _event: &Event,
+        _error: &anyhow::Error,
+        _attempts: u32,
+    ) {
+    }
+    /// Called when a transaction keeps returning a `TransactionRetryError` and the
+    /// transaction [`RetryPolicy`][crate::retry::RetryPolicy] allows no more attempts.
+    /// The processor stops with an unrecoverable error after this.
+    async fn transaction_retries_exhausted(
+        &mut self,
+        _transaction: &Transaction,
+        _error: &anyhow::Error,
+        _attempts: u32,
+    ) {
+    }
+    /// Called when an event failed and was parked in the
+    /// [`DeadLetterSink`][crate::dead_letters::DeadLetterSink],
+    /// after which processing continues with the next event.
+    async fn event_parked(
+        &mut self,
+        _transaction: &Transaction,
+        _event: &Event,
+        _error: &anyhow::Error,
+        _attempts: u32,
+    ) {
+    }
     /// Called when an `UnrecoverableError` is returned from a handler
     /// and the processor should stop processing.
     async fn unrecoverable_error(&mut self, error: &anyhow::Error);
+    /// Called when the transaction stream failed to fetch from its source,
+    /// like a Gateway that is unavailable or rate limiting. `retry_in` is
+    /// the delay before the stream tries again, or `None` when it gives up,
+    /// which ends the stream. Only streams that report their errors call this,
+    /// see [`TransactionStream::set_logger`][crate::stream::TransactionStream::set_logger].
+    async fn stream_error(
+        &mut self,
+        _source: &str,
+        _error: &anyhow::Error,
+        _kind: StreamErrorKind,
+        _retry_in: Option<Duration>,
+    ) {
+    }
+    /// Called when a stream that was set to start at a timestamp, with
+    /// `from_timestamp` on the Gateway and database streams, resolved it
+    /// to the state version of the first transaction confirmed at or
+    /// after it, which is where the stream starts.
+    async fn stream_timestamp_resolved(
+        &mut self,
+        _timestamp: DateTime<Utc>,
+        _state_version: u64,
+    ) {
+    }
     /// Called periodically by an independent task. This is useful for
     /// logging and metric collection. It is possible to set a custom
     /// interval by implementing the `periodic_report_interval` method.
@@ -202,7 +283,12 @@ impl Logger for DefaultLogger {
                     .expect("When handling a transaction it should always have a timestamp")
                     .format("%a %d-%m-%Y %H:%M")
             ).bright_green();
-            let transaction_id = transaction.intent_hash.bright_green();
+            let transaction_id = if transaction.intent_hash.is_empty() {
+                transaction.kind.as_str()
+            } else {
+                &transaction.intent_hash
+            }
+            .bright_green();
             info!("{}", message);
             info!("{}", transaction_id);
         }
@@ -312,11 +398,98 @@ impl Logger for DefaultLogger {
         info!("{}", retry_message);
     }
 
+    async fn event_retries_exhausted(
+        &mut self,
+        _transaction: &Transaction,
+        event: &Event,
+        error: &anyhow::Error,
+        attempts: u32,
+    ) {
+        let message = format!(
+            "GAVE UP ON EVENT: {} AFTER {} ATTEMPTS - {:?}",
+            event.name, attempts, error
+        )
+        .bright_red();
+        error!("{}", message);
+    }
+
+    async fn transaction_retries_exhausted(
+        &mut self,
+        transaction: &Transaction,
+        error: &anyhow::Error,
+        attempts: u32,
+    ) {
+        let message = format!(
+            "GAVE UP ON TRANSACTION: {} AFTER {} ATTEMPTS - {:?}",
+            transaction.state_version, attempts, error
+        )
+        .bright_red();
+        error!("{}", message);
+    }
+
+    async fn event_parked(
+        &mut self,
+        transaction: &Transaction,
+        event: &Event,
+        error: &anyhow::Error,
+        attempts: u32,
+    ) {
+        let message = format!(
+            "PARKED EVENT: {} IN TRANSACTION {} AFTER {} ATTEMPTS - {:?}",
+            event.name, transaction.state_version, attempts, error
+        )
+        .bright_red();
+        error!("{}", message);
+    }
+
     async fn unrecoverable_error(&mut self, error: &anyhow::Error) {
         let message = format!("UNRECOVERABLE ERROR: {:?}", error).bright_red();
         error!("{}", message);
     }
 
+    async fn stream_error(
+        &mut self,
+        source: &str,
+        error: &anyhow::Error,
+        kind: StreamErrorKind,
+        retry_in: Option<Duration>,
+    ) {
+        let kind = match kind {
+            StreamErrorKind::Transient => "ERROR",
+            StreamErrorKind::RateLimited => "RATE LIMITED",
+            StreamErrorKind::Permanent => "PERMANENT ERROR",
+        };
+        let message = format!("{} FETCHING FROM {}: {:?}", kind, source, error);
+        match retry_in {
+            Some(retry_in) => {
+                let retry_message = format!(
+                    "RETRYING IN {:.1} SECONDS\n",
+                    retry_in.as_secs_f32()
+                )
+                .bright_yellow();
+                warn!("{}", message.bright_yellow());
+                info!("{}", retry_message);
+            }
+            None => {
+                error!("{}", format!("{} - GIVING UP", message).bright_red());
+            }
+        }
+    }
+
+    async fn stream_timestamp_resolved(
+        &mut self,
+        timestamp: DateTime<Utc>,
+        state_version: u64,
+    ) {
+        let message = format!(
+            "STARTING AT STATE VERSION {} - FIRST TRANSACTION AT OR AFTER {}",
+            state_version,
+            timestamp.format("%a %d-%m-%Y %H:%M:%S")
+        )
+        .bright_green();
+        info!("{}", message);
+    }
+
     async fn periodic_report(&self) {
         match self.metrics.last_seen_state_version {
             Some(state_version) => {
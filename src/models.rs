@@ -32,6 +32,10 @@ pub enum EventEmitter {
         entity_type: EntityType,
         is_global: bool,
         object_module_id: ModuleId,
+        /// The blueprint the emitting entity was instantiated from.
+        /// This is `None` when the source couldn't resolve it.
+        #[serde(default)]
+        blueprint: Option<BlueprintId>,
     },
     Function {
         package_address: String,
@@ -49,6 +53,31 @@ impl EventEmitter {
             } => package_address,
         }
     }
+
+    /// Returns the package address and blueprint name of the emitter, when known.
+    pub fn blueprint(&self) -> Option<(&str, &str)> {
+        match self {
+            EventEmitter::Method { blueprint, .. } => {
+                blueprint.as_ref().map(|blueprint| {
+                    (
+                        blueprint.package_address.as_str(),
+                        blueprint.blueprint_name.as_str(),
+                    )
+                })
+            }
+            EventEmitter::Function {
+                package_address,
+                blueprint_name,
+            } => Some((package_address, blueprint_name)),
+        }
+    }
+}
+
+/// Identifies a blueprint by the address of its package and its name.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct BlueprintId {
+    pub package_address: String,
+    pub blueprint_name: String,
 }
 
 /// Generic struct for ledger transactions from a
@@ -57,8 +86,84 @@ impl EventEmitter {
 /// for the native transaction type of the transaction stream.
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct Transaction {
+    /// The intent hash of the transaction. Only user transactions
+    /// have an intent hash, for other kinds this is empty.
     pub intent_hash: String,
     pub state_version: u64,
+    #[serde(default)]
+    pub kind: TransactionKind,
+    #[serde(default)]
+    pub status: TransactionStatus,
+    /// Explains why the transaction failed, if it did.
+    #[serde(default)]
+    pub error_message: Option<String>,
     pub confirmed_at: Option<chrono::DateTime<Utc>>,
     pub events: Vec<Event>,
 }
+
+/// The kind of a ledger transaction. Most transactions are submitted by
+/// users, but the network also commits transactions of its own, like
+/// round updates, which emit events such as round and epoch changes.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionKind {
+    #[default]
+    User,
+    RoundUpdate,
+    Genesis,
+    Flash,
+}
+
+impl TransactionKind {
+    /// All transaction kinds.
+    pub const ALL: [TransactionKind; 4] = [
+        TransactionKind::User,
+        TransactionKind::RoundUpdate,
+        TransactionKind::Genesis,
+        TransactionKind::Flash,
+    ];
+
+    /// Returns the name of the kind, as used by the Gateway
+    /// database in the `discriminator` column.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransactionKind::User => "user",
+            TransactionKind::RoundUpdate => "round_update",
+            TransactionKind::Genesis => "genesis",
+            TransactionKind::Flash => "flash",
+        }
+    }
+}
+
+impl std::str::FromStr for TransactionKind {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        TransactionKind::ALL
+            .into_iter()
+            .find(|kind| kind.as_str() == s)
+            .ok_or_else(|| anyhow::anyhow!("Unknown transaction kind: {}", s))
+    }
+}
+
+/// The outcome of a transaction. Failed transactions are still committed
+/// to the ledger, and emit events like the `LockFeeEvent` and `PayFeeEvent`.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize, Default,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum TransactionStatus {
+    #[default]
+    CommittedSuccess,
+    CommittedFailure,
+    Rejected,
+}
+
+impl TransactionStatus {
+    /// Returns whether the transaction was committed successfully.
+    pub fn is_success(&self) -> bool {
+        matches!(self, TransactionStatus::CommittedSuccess)
+    }
+}
@@ -6,20 +6,29 @@ a default implementation of a [`TransactionHandler`], and a struct that processe
 */
 
 use crate::{
+    checkpoints::CheckpointStore,
+    dead_letters::{DeadLetter, DeadLetterSink, ParkedDeadLetter},
     error::{
         EventHandlerError, TransactionHandlerError, TransactionProcessorError,
     },
     event_handler::{EventHandlerContext, HandlerRegistry, State},
     logger::{DefaultLogger, Logger},
-    models::{EventEmitter, Transaction},
-    native_events::NativeEventType,
+    models::Transaction,
+    retry::RetryPolicy,
     stream::TransactionStream,
-    transaction_handler::{TransactionHandler, TransactionHandlerContext},
+    transaction_handler::{
+        BatchTransactionHandler, BatchTransactionHandlerContext,
+        TransactionHandler, TransactionHandlerContext,
+    },
 };
 use async_trait::async_trait;
 use core::panic;
-use std::{sync::Arc, time::Duration};
-use tokio::sync::RwLock;
+use std::{
+    any::Any,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use tokio::sync::{watch, Mutex, RwLock};
 
 /// The main struct that processes transactions from a [`TransactionStream`].
 /// It processes transactions by having an instance of [`TransactionProcessor`], and passing transactions to it.
@@ -28,6 +37,9 @@ use tokio::sync::RwLock;
 ///
 /// If you don't set a transaction handler explicitly, the processor will use a default handler
 /// that simply calls [`EventProcessor::process_events`] on the transaction, without any custom logic.
+///
+/// The processor can be stopped gracefully through a [`ShutdownHandle`], or on SIGINT/SIGTERM
+/// by using [`TransactionStreamProcessor::shutdown_on_signals`].
 #[allow(non_camel_case_types)]
 pub struct TransactionStreamProcessor<STREAM, STATE>
 where
@@ -37,6 +49,37 @@ where
     transaction_processor: TransactionProcessor<STATE>,
     transaction_stream: STREAM,
     periodic_logging_joinhandle: Option<tokio::task::JoinHandle<()>>,
+    shutdown_sender: Arc<watch::Sender<bool>>,
+    shutdown_on_signals: bool,
+    batch_size: usize,
+    batch_linger: Duration,
+}
+
+/// A handle to request a graceful shutdown of a running [`TransactionStreamProcessor`].
+/// It can be cloned and moved to other tasks.
+///
+/// When a shutdown is requested, the processor lets the transaction it is currently
+/// processing finish, stops the stream and returns from [`TransactionStreamProcessor::run`]
+/// with the last fully processed state version.
+#[derive(Clone, Debug)]
+pub struct ShutdownHandle {
+    sender: Arc<watch::Sender<bool>>,
+}
+
+impl ShutdownHandle {
+    pub(crate) fn new(sender: Arc<watch::Sender<bool>>) -> Self {
+        Self { sender }
+    }
+
+    /// Requests the processor to shut down after the current transaction.
+    pub fn shutdown(&self) {
+        self.sender.send_replace(true);
+    }
+
+    /// Returns whether a shutdown has been requested.
+    pub fn is_shutdown(&self) -> bool {
+        *self.sender.borrow()
+    }
 }
 
 #[allow(non_camel_case_types)]
@@ -57,6 +100,8 @@ where
     ///
     /// - The logger is set to a default logger that logs to stdout.
     ///
+    /// - No checkpoint store is set, so progress is not persisted.
+    ///
     /// Change the default handler, retry intervals, or logger using
     /// the builder methods.
     pub fn new(
@@ -64,20 +109,16 @@ where
         handler_registry: HandlerRegistry,
         state: STATE,
     ) -> Self {
-        let transaction_processor = TransactionProcessor {
-            logger: Some(Arc::new(
-                RwLock::new(Box::<DefaultLogger>::default()),
-            )),
-            transaction_handler: Box::new(DefaultTransactionHandler),
-            transaction_retry_delay: Duration::from_secs(10),
-            event_retry_delay: Duration::from_secs(10),
-            handler_registry: handler_registry,
-            state: state,
-        };
+        let transaction_processor =
+            TransactionProcessor::new(handler_registry, state);
         Self {
             transaction_stream,
             transaction_processor,
             periodic_logging_joinhandle: None,
+            shutdown_sender: Arc::new(watch::channel(false).0),
+            shutdown_on_signals: false,
+            batch_size: 100,
+            batch_linger: Duration::from_millis(100),
         }
     }
 
@@ -96,8 +137,43 @@ where
         }
     }
 
+    /// Sets a [`BatchTransactionHandler`] for the processor, which is used instead of
+    /// the [`TransactionHandler`]. Transactions are collected into batches of up to
+    /// `batch_size` transactions, and a batch is handled early when its first transaction
+    /// has waited for `batch_linger`. See [`crate::transaction_handler`] for details.
+    pub fn batch_transaction_handler(
+        self,
+        batch_transaction_handler: impl BatchTransactionHandler<STATE>,
+    ) -> Self {
+        Self {
+            transaction_processor: self
+                .transaction_processor
+                .batch_transaction_handler(batch_transaction_handler),
+            ..self
+        }
+    }
+
+    /// Sets the max number of transactions in a batch for the
+    /// [`BatchTransactionHandler`]. Defaults to 100.
+    pub fn batch_size(self, batch_size: usize) -> Self {
+        Self {
+            batch_size: batch_size.max(1),
+            ..self
+        }
+    }
+
+    /// Sets the max time a transaction waits for its batch to fill up
+    /// before the batch is handled anyway. Defaults to 100 milliseconds.
+    pub fn batch_linger(self, batch_linger: Duration) -> Self {
+        Self {
+            batch_linger,
+            ..self
+        }
+    }
+
     /// Sets the retry delay for transactions that fail to process and return a `TransactionRetryError`
     /// (see [`crate::error::TransactionHandlerError`]).
+    /// This is a shorthand for a [`RetryPolicy::fixed`] policy which retries forever.
     pub fn transaction_retry_delay(
         self,
         transaction_retry_delay: Duration,
@@ -112,6 +188,7 @@ where
 
     /// Sets the retry delay for events that fail to process and return an `EventRetryError`.
     /// (see [`crate::error::EventHandlerError`]).
+    /// This is a shorthand for a [`RetryPolicy::fixed`] policy which retries forever.
     pub fn event_retry_delay(self, event_retry_delay: Duration) -> Self {
         Self {
             transaction_processor: self
@@ -121,6 +198,32 @@ where
         }
     }
 
+    /// Sets the [`RetryPolicy`] for transactions that fail to process and return a `TransactionRetryError`.
+    /// When the policy allows no more attempts, the processor stops with an unrecoverable error.
+    pub fn transaction_retry_policy(
+        self,
+        transaction_retry_policy: RetryPolicy,
+    ) -> Self {
+        Self {
+            transaction_processor: self
+                .transaction_processor
+                .transaction_retry_policy(transaction_retry_policy),
+            ..self
+        }
+    }
+
+    /// Sets the default [`RetryPolicy`] for events that fail to process and return an `EventRetryError`.
+    /// When the policy allows no more attempts, the whole transaction is retried instead.
+    /// It can be overridden per matcher using [`HandlerRegistry::set_retry_policy`].
+    pub fn event_retry_policy(self, event_retry_policy: RetryPolicy) -> Self {
+        Self {
+            transaction_processor: self
+                .transaction_processor
+                .event_retry_policy(event_retry_policy),
+            ..self
+        }
+    }
+
     /// Sets the logger for the processor. It should implement the [`Logger`] trait.
     pub fn logger(self, logger: impl Logger + 'static) -> Self {
         Self {
@@ -151,8 +254,114 @@ where
         }
     }
 
+    /// Sets a [`CheckpointStore`] which is updated with the state version of
+    /// each finished transaction. When the processor is started, the stream
+    /// resumes from the state version after the last stored checkpoint.
+    pub fn checkpoint_store(
+        self,
+        checkpoint_store: impl CheckpointStore + 'static,
+    ) -> Self {
+        Self {
+            transaction_processor: self
+                .transaction_processor
+                .checkpoint_store(checkpoint_store),
+            ..self
+        }
+    }
+
+    /// Sets the minimum interval between checkpoints for transactions
+    /// that had no events to handle. Transactions that were handled are
+    /// always checkpointed directly. Defaults to 5 seconds.
+    pub fn checkpoint_interval(self, checkpoint_interval: Duration) -> Self {
+        Self {
+            transaction_processor: self
+                .transaction_processor
+                .checkpoint_interval(checkpoint_interval),
+            ..self
+        }
+    }
+
+    /// Sets a [`DeadLetterSink`] in which events are parked when their handler keeps failing,
+    /// so that the processor can continue with the next event.
+    /// See [`crate::dead_letters`] for when events are parked.
+    ///
+    /// Note that the default event retry policy retries forever, so events that return an
+    /// `EventRetryError` are only parked with a policy that has
+    /// [`RetryPolicy::max_attempts`] set, see [`TransactionStreamProcessor::event_retry_policy`].
+    pub fn dead_letter_sink(
+        self,
+        dead_letter_sink: impl DeadLetterSink + 'static,
+    ) -> Self {
+        Self {
+            transaction_processor: self
+                .transaction_processor
+                .dead_letter_sink(dead_letter_sink),
+            ..self
+        }
+    }
+
+    /// Restores the state to its value from before the transaction, each time
+    /// the transaction is retried and before an unrecoverable error is returned.
+    /// This way, changes that event handlers made to the state before the
+    /// failure are not applied twice.
+    ///
+    /// When a [`DeadLetterSink`] is set, the changes a handler made before
+    /// its event was parked are undone in the same way. Otherwise they are kept.
+    ///
+    /// The state is cloned at the start of each transaction that is handled,
+    /// and before each handler when a [`DeadLetterSink`] is set,
+    /// so this is best used with states that are cheap to clone.
+    pub fn rollback_state_on_retry(self) -> Self
+    where
+        STATE: Clone,
+    {
+        Self {
+            transaction_processor: self
+                .transaction_processor
+                .rollback_state_on_retry(),
+            ..self
+        }
+    }
+
+    /// Makes the processor shut down gracefully when the process receives
+    /// SIGINT (Ctrl+C) or, on Unix, SIGTERM.
+    pub fn shutdown_on_signals(self) -> Self {
+        Self {
+            shutdown_on_signals: true,
+            ..self
+        }
+    }
+
+    /// Returns a [`ShutdownHandle`] which can be used to stop
+    /// the processor gracefully while it is running.
+    pub fn shutdown_handle(&self) -> ShutdownHandle {
+        ShutdownHandle {
+            sender: self.shutdown_sender.clone(),
+        }
+    }
+
     /// Starts processing transactions from the [`TransactionStream`].
-    pub async fn run(&mut self) -> Result<(), TransactionProcessorError> {
+    ///
+    /// Returns the state version of the last fully processed transaction when
+    /// the stream is finished or a shutdown was requested through a [`ShutdownHandle`].
+    pub async fn run(
+        &mut self,
+    ) -> Result<Option<u64>, TransactionProcessorError> {
+        // If a checkpoint was stored by a previous run, continue
+        // right after it instead of at the configured state version.
+        if let Some(state_version) =
+            self.transaction_processor.resume_state_version().await?
+        {
+            log::info!(
+                "Resuming from checkpoint at state version {}",
+                state_version
+            );
+            self.transaction_stream
+                .resume_from_state_version(state_version);
+        }
+        if let Some(logger) = &self.transaction_processor.logger {
+            self.transaction_stream.set_logger(logger.clone());
+        }
         // Start the transaction stream and get a receiver.
         // This often involves starting a task that fetches transactions
         // from a remote source and sends them to the receiver.
@@ -172,19 +381,164 @@ where
         } else {
             None
         };
+        let signal_joinhandle = if self.shutdown_on_signals {
+            let shutdown_handle = self.shutdown_handle();
+            Some(tokio::spawn(async move {
+                wait_for_shutdown_signal().await;
+                shutdown_handle.shutdown();
+            }))
+        } else {
+            None
+        };
+        let mut shutdown_receiver = self.shutdown_sender.subscribe();
+        // Transactions waiting to be handled by the batch transaction handler,
+        // and the time at which the batch is handled even if it's not full.
+        let mut batch = Vec::new();
+        let mut batch_deadline = None;
+        let mut stream_error = None;
         // Process transactions as they arrive.
-        while let Some(transaction) = receiver.recv().await {
-            self.transaction_processor
-                .process_transaction(&transaction)
-                .await?;
-        }
-        // If the transmitting half of the channel is dropped,
-        // the receiver will return None and we will exit the loop.
-        // The processor will exit gracefully.
+        let result = loop {
+            // Only wait for a shutdown in between transactions,
+            // so the in-flight transaction is always finished.
+            let transaction = tokio::select! {
+                biased;
+                _ = shutdown_receiver.wait_for(|shutdown| *shutdown) => {
+                    log::info!("Shutdown requested, stopping the processor");
+                    break Ok(());
+                }
+                _ = sleep_until_deadline(batch_deadline) => {
+                    batch_deadline = None;
+                    match self.transaction_processor.process_batch(&batch).await {
+                        Ok(()) => batch.clear(),
+                        Err(err) => break Err(err),
+                    }
+                    continue;
+                }
+                transaction = receiver.recv() => match transaction {
+                    Some(transaction) => transaction,
+                    // If the transmitting half of the channel is dropped,
+                    // the receiver will return None and we will exit the loop.
+                    // The processor will exit gracefully, unless the stream
+                    // gave up on its source.
+                    None => {
+                        stream_error = self.transaction_stream.take_error();
+                        break Ok(());
+                    }
+                },
+            };
+            if self
+                .transaction_processor
+                .batch_transaction_handler
+                .is_none()
+            {
+                if let Err(err) = self
+                    .transaction_processor
+                    .process_transaction(&transaction)
+                    .await
+                {
+                    break Err(err);
+                }
+                continue;
+            }
+            if batch.is_empty() {
+                batch_deadline =
+                    Some(tokio::time::Instant::now() + self.batch_linger);
+            }
+            batch.push(transaction);
+            if batch.len() >= self.batch_size {
+                batch_deadline = None;
+                if let Err(err) =
+                    self.transaction_processor.process_batch(&batch).await
+                {
+                    break Err(err);
+                }
+                batch.clear();
+            }
+        };
+        // Transactions that were already received are still handled
+        // when the stream ends or a shutdown is requested.
+        let result = match result {
+            Ok(()) => self.transaction_processor.process_batch(&batch).await,
+            Err(err) => Err(err),
+        };
+        // The transactions the stream delivered before it gave up
+        // are handled, but the processor still fails.
+        let result = match stream_error {
+            Some(err) if result.is_ok() => {
+                Err(TransactionProcessorError::UnrecoverableError(err))
+            }
+            _ => result,
+        };
+        self.stop(signal_joinhandle).await;
+        // On failure, persist progress up to the failed transaction, so
+        // a restart doesn't redo work that was already finished.
+        let saved = self.transaction_processor.save_checkpoint().await;
+        match (result, saved) {
+            (Ok(()), Ok(())) => {
+                Ok(self.transaction_processor.last_committed_state_version())
+            }
+            (Ok(()), Err(err)) => Err(err),
+            // The processing error is what stopped the processor,
+            // so it takes precedence over failing to save.
+            (Err(err), saved) => {
+                if let Err(save_err) = saved {
+                    log::error!(
+                        "Failed to save checkpoint after processing failed: {:?}",
+                        save_err
+                    );
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Stops the background tasks and the stream after processing has ended.
+    async fn stop(
+        &mut self,
+        signal_joinhandle: Option<tokio::task::JoinHandle<()>>,
+    ) {
         if let Some(handle) = self.periodic_logging_joinhandle.take() {
             handle.abort();
         }
-        Ok(())
+        if let Some(handle) = signal_joinhandle {
+            handle.abort();
+        }
+        self.transaction_stream.stop().await;
+    }
+}
+
+/// Resolves at the deadline, or never if there is none.
+async fn sleep_until_deadline(deadline: Option<tokio::time::Instant>) {
+    match deadline {
+        Some(deadline) => tokio::time::sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Resolves when the process receives SIGINT or, on Unix, SIGTERM.
+pub(crate) async fn wait_for_shutdown_signal() {
+    #[cfg(unix)]
+    {
+        use tokio::signal::unix::{signal, SignalKind};
+        let mut terminate = match signal(SignalKind::terminate()) {
+            Ok(terminate) => terminate,
+            Err(err) => {
+                log::error!(
+                    "Failed to install SIGTERM handler, only waiting for Ctrl-C: {:?}",
+                    err
+                );
+                let _ = tokio::signal::ctrl_c().await;
+                return;
+            }
+        };
+        tokio::select! {
+            _ = tokio::signal::ctrl_c() => {}
+            _ = terminate.recv() => {}
+        }
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = tokio::signal::ctrl_c().await;
     }
 }
 
@@ -224,9 +578,19 @@ pub struct TransactionProcessor<STATE: State> {
     pub logger: Option<Arc<RwLock<Box<dyn Logger>>>>,
     pub handler_registry: HandlerRegistry,
     pub transaction_handler: Box<dyn TransactionHandler<STATE>>,
+    pub batch_transaction_handler:
+        Option<Box<dyn BatchTransactionHandler<STATE>>>,
     pub state: STATE,
-    pub transaction_retry_delay: Duration,
-    pub event_retry_delay: Duration,
+    pub transaction_retry_policy: RetryPolicy,
+    pub event_retry_policy: RetryPolicy,
+    pub checkpoint_store: Option<Box<dyn CheckpointStore>>,
+    pub checkpoint_interval: Duration,
+    pub dead_letter_sink: Option<Arc<Mutex<Box<dyn DeadLetterSink>>>>,
+    clone_state: Option<fn(&STATE) -> STATE>,
+    snapshot_state: Option<SnapshotState>,
+    last_committed_state_version: Option<u64>,
+    last_saved_state_version: Option<u64>,
+    last_checkpoint_time: Instant,
 }
 
 #[allow(non_camel_case_types)]
@@ -237,10 +601,19 @@ impl<STATE: State> TransactionProcessor<STATE> {
                 RwLock::new(Box::<DefaultLogger>::default()),
             )),
             transaction_handler: Box::new(DefaultTransactionHandler),
-            transaction_retry_delay: Duration::from_secs(10),
-            event_retry_delay: Duration::from_secs(10),
+            batch_transaction_handler: None,
+            transaction_retry_policy: RetryPolicy::default(),
+            event_retry_policy: RetryPolicy::default(),
             handler_registry: handler_registry,
             state: state,
+            checkpoint_store: None,
+            checkpoint_interval: Duration::from_secs(5),
+            dead_letter_sink: None,
+            clone_state: None,
+            snapshot_state: None,
+            last_committed_state_version: None,
+            last_saved_state_version: None,
+            last_checkpoint_time: Instant::now(),
         }
     }
 
@@ -249,14 +622,33 @@ impl<STATE: State> TransactionProcessor<STATE> {
         transaction_retry_delay: Duration,
     ) -> Self {
         Self {
-            transaction_retry_delay,
+            transaction_retry_policy: RetryPolicy::fixed(
+                transaction_retry_delay,
+            ),
             ..self
         }
     }
 
     pub fn event_retry_delay(self, event_retry_delay: Duration) -> Self {
         Self {
-            event_retry_delay,
+            event_retry_policy: RetryPolicy::fixed(event_retry_delay),
+            ..self
+        }
+    }
+
+    pub fn transaction_retry_policy(
+        self,
+        transaction_retry_policy: RetryPolicy,
+    ) -> Self {
+        Self {
+            transaction_retry_policy,
+            ..self
+        }
+    }
+
+    pub fn event_retry_policy(self, event_retry_policy: RetryPolicy) -> Self {
+        Self {
+            event_retry_policy,
             ..self
         }
     }
@@ -271,6 +663,18 @@ impl<STATE: State> TransactionProcessor<STATE> {
         }
     }
 
+    pub fn batch_transaction_handler(
+        self,
+        batch_transaction_handler: impl BatchTransactionHandler<STATE>,
+    ) -> Self {
+        Self {
+            batch_transaction_handler: Some(Box::new(
+                batch_transaction_handler,
+            )),
+            ..self
+        }
+    }
+
     pub fn logger(self, logger: impl Logger + 'static) -> Self {
         Self {
             logger: Some(Arc::new(RwLock::new(Box::new(logger)))),
@@ -285,16 +689,185 @@ impl<STATE: State> TransactionProcessor<STATE> {
         }
     }
 
+    pub fn checkpoint_store(
+        self,
+        checkpoint_store: impl CheckpointStore + 'static,
+    ) -> Self {
+        Self {
+            checkpoint_store: Some(Box::new(checkpoint_store)),
+            ..self
+        }
+    }
+
+    pub fn checkpoint_interval(self, checkpoint_interval: Duration) -> Self {
+        Self {
+            checkpoint_interval,
+            ..self
+        }
+    }
+
+    pub fn dead_letter_sink(
+        self,
+        dead_letter_sink: impl DeadLetterSink + 'static,
+    ) -> Self {
+        Self {
+            dead_letter_sink: Some(Arc::new(Mutex::new(Box::new(
+                dead_letter_sink,
+            )))),
+            ..self
+        }
+    }
+
+    /// Enables restoring the state to a snapshot taken at the start of each transaction
+    /// when it is retried, or when it fails with an unrecoverable error.
+    pub fn rollback_state_on_retry(self) -> Self
+    where
+        STATE: Clone,
+    {
+        Self {
+            clone_state: Some(STATE::clone),
+            snapshot_state: Some(snapshot_state::<STATE>),
+            ..self
+        }
+    }
+
+    /// The state version of the last transaction that was fully processed,
+    /// whether it has been written to the [`CheckpointStore`] yet or not.
+    pub fn last_committed_state_version(&self) -> Option<u64> {
+        self.last_committed_state_version
+    }
+
+    /// Loads the state version to resume from out of the [`CheckpointStore`],
+    /// if one is set and holds a checkpoint.
+    pub async fn resume_state_version(
+        &self,
+    ) -> Result<Option<u64>, TransactionProcessorError> {
+        match &self.checkpoint_store {
+            Some(checkpoint_store) => checkpoint_store
+                .resume_state_version()
+                .await
+                .map_err(TransactionProcessorError::UnrecoverableError),
+            None => Ok(None),
+        }
+    }
+
+    /// Writes the last committed state version to the [`CheckpointStore`],
+    /// if it wasn't written yet. Failing to save a checkpoint is unrecoverable,
+    /// because continuing would make the stored progress diverge from
+    /// the work that was actually done.
+    pub async fn save_checkpoint(
+        &mut self,
+    ) -> Result<(), TransactionProcessorError> {
+        let (Some(checkpoint_store), Some(state_version)) = (
+            &mut self.checkpoint_store,
+            self.last_committed_state_version,
+        ) else {
+            return Ok(());
+        };
+        if self.last_saved_state_version == Some(state_version) {
+            return Ok(());
+        }
+        if let Err(e) = checkpoint_store.save(state_version).await {
+            if let Some(logger) = &self.logger {
+                logger.write().await.unrecoverable_error(&e).await;
+            }
+            return Err(TransactionProcessorError::UnrecoverableError(e));
+        }
+        self.last_saved_state_version = Some(state_version);
+        self.last_checkpoint_time = Instant::now();
+        Ok(())
+    }
+
+    /// Marks a transaction as committed. Handled transactions are checkpointed
+    /// directly, other transactions only once per `checkpoint_interval`.
+    ///
+    /// Events that failed are only parked here, once their transaction
+    /// is done, so a transaction that is retried doesn't park them twice.
+    async fn commit_transaction(
+        &mut self,
+        transaction: &Transaction,
+        handled: bool,
+        dead_letters: Vec<PendingDeadLetter>,
+    ) -> Result<(), TransactionProcessorError> {
+        for dead_letter in dead_letters {
+            self.park(dead_letter).await?;
+        }
+        self.last_committed_state_version = Some(transaction.state_version);
+        if handled
+            || self.last_checkpoint_time.elapsed() >= self.checkpoint_interval
+        {
+            self.save_checkpoint().await?;
+        }
+        Ok(())
+    }
+
+    /// Stores a failed event in the [`DeadLetterSink`]. If that fails too,
+    /// the processor can't continue, so an unrecoverable error is returned.
+    async fn park(
+        &self,
+        pending: PendingDeadLetter,
+    ) -> Result<(), TransactionProcessorError> {
+        let Some(dead_letter_sink) = &self.dead_letter_sink else {
+            return Err(TransactionProcessorError::UnrecoverableError(
+                pending.error,
+            ));
+        };
+        let PendingDeadLetter { dead_letter, error } = pending;
+        if let Err(park_error) = dead_letter_sink
+            .lock()
+            .await
+            .park(dead_letter.clone())
+            .await
+        {
+            let e = park_error.context(format!(
+                "Failed to park event {} after: {:?}",
+                dead_letter.event.name, error
+            ));
+            if let Some(logger) = &self.logger {
+                logger.write().await.unrecoverable_error(&e).await;
+            }
+            return Err(TransactionProcessorError::UnrecoverableError(e));
+        }
+        if let Some(logger) = &self.logger {
+            logger
+                .write()
+                .await
+                .event_parked(
+                    &dead_letter.transaction,
+                    &dead_letter.event,
+                    &error,
+                    dead_letter.attempts,
+                )
+                .await;
+        }
+        Ok(())
+    }
+
     pub async fn process_transaction(
         &mut self,
         transaction: &Transaction,
+    ) -> Result<(), TransactionProcessorError> {
+        self.process_transaction_events(transaction, None).await
+    }
+
+    /// Processes a transaction like [`TransactionProcessor::process_transaction`],
+    /// but only handles the events at the given indices, if set. The transaction
+    /// handler is still called for the transaction as a whole.
+    ///
+    /// This is used to split the events of a transaction over multiple processors.
+    pub async fn process_transaction_events(
+        &mut self,
+        transaction: &Transaction,
+        event_indices: Option<&[u16]>,
     ) -> Result<(), TransactionProcessorError> {
         // Find out if there are any events inside this transaction
         // that have a handler registered.
-        let handler_exists = transaction
-            .events
-            .iter()
-            .any(|event| self.handler_registry.handler_exists(event));
+        let handler_exists =
+            transaction.events.iter().enumerate().any(|(index, event)| {
+                event_indices.map_or(true, |event_indices| {
+                    event_indices.contains(&(index as u16))
+                }) && self.handler_registry.handles_event(transaction, event)
+            });
 
         if let Some(logger) = &self.logger {
             logger
@@ -314,70 +887,268 @@ impl<STATE: State> TransactionProcessor<STATE> {
                     .finish_transaction(transaction, false)
                     .await;
             }
-            return Ok(());
+            return self
+                .commit_transaction(transaction, false, Vec::new())
+                .await;
         }
 
+        // Take a snapshot of the state so changes made by a failed
+        // attempt can be undone, if rollbacks are enabled.
+        let snapshot =
+            self.clone_state.map(|clone_state| clone_state(&self.state));
+
         // Keep trying to handle the transaction in case
-        // the handler requests this through a TransactionHandlerError.
+        // the handler requests this through a TransactionHandlerError,
+        // for as long as the retry policy allows it.
+        let dead_letters = std::sync::Mutex::new(Vec::new());
+        let mut failed_attempts = 0;
         while let Err(err) = self
             .transaction_handler
             .handle(TransactionHandlerContext {
                 state: &mut self.state,
                 transaction,
                 event_processor: &mut EventProcessor {
-                    event_retry_interval: self.event_retry_delay,
+                    event_retry_policy: &self.event_retry_policy,
                     transaction,
                     logger: &self.logger,
+                    dead_letter_sink: &self.dead_letter_sink,
+                    dead_letters: &dead_letters,
+                    snapshot_state: self.snapshot_state,
+                    only_event_indices: event_indices,
                 },
                 handler_registry: &mut self.handler_registry,
             })
             .await
         {
-            match err {
+            let e = match err {
                 TransactionHandlerError::TransactionRetryError(e) => {
+                    failed_attempts += 1;
+                    if self
+                        .transaction_retry_policy
+                        .should_retry(failed_attempts)
+                    {
+                        let delay = self
+                            .transaction_retry_policy
+                            .delay(failed_attempts);
+                        if let Some(logger) = &self.logger {
+                            logger
+                                .write()
+                                .await
+                                .transaction_retry_error(transaction, &e, delay)
+                                .await;
+                        }
+                        if let (Some(clone_state), Some(snapshot)) =
+                            (self.clone_state, &snapshot)
+                        {
+                            self.state = clone_state(snapshot);
+                        }
+                        // The events are handled again, so any that
+                        // fail again are parked on the next attempt.
+                        dead_letters.lock().unwrap().clear();
+                        tokio::time::sleep(delay).await;
+                        if let Some(logger) = &self.logger {
+                            logger
+                                .write()
+                                .await
+                                .receive_transaction(
+                                    transaction,
+                                    handler_exists,
+                                    true,
+                                )
+                                .await;
+                        }
+                        continue;
+                    }
+                    // The retry policy gave up on this transaction,
+                    // so escalate to an unrecoverable error.
                     if let Some(logger) = &self.logger {
                         logger
                             .write()
                             .await
-                            .transaction_retry_error(
+                            .transaction_retries_exhausted(
                                 transaction,
                                 &e,
-                                self.transaction_retry_delay,
+                                failed_attempts,
                             )
                             .await;
                     }
-                    tokio::time::sleep(self.transaction_retry_delay).await;
+                    e.context(format!(
+                        "Transaction {} failed after {} attempts",
+                        transaction.state_version, failed_attempts
+                    ))
+                }
+                TransactionHandlerError::UnrecoverableError(e) => e,
+            };
+            if let Some(logger) = &self.logger {
+                logger.write().await.unrecoverable_error(&e).await;
+            }
+            if let Some(snapshot) = snapshot {
+                self.state = snapshot;
+            }
+            return Err(TransactionProcessorError::UnrecoverableError(e));
+        }
+        if let Some(logger) = &self.logger {
+            logger
+                .write()
+                .await
+                .finish_transaction(transaction, true)
+                .await;
+        }
+        self.commit_transaction(
+            transaction,
+            true,
+            dead_letters.into_inner().unwrap(),
+        )
+        .await
+    }
+
+    /// Processes a batch of consecutive transactions with the [`BatchTransactionHandler`].
+    /// Retries apply to the batch as a whole, and the batch is only committed
+    /// once all its transactions are handled.
+    ///
+    /// Returns an error if no [`BatchTransactionHandler`] is set.
+    pub async fn process_batch(
+        &mut self,
+        transactions: &[Transaction],
+    ) -> Result<(), TransactionProcessorError> {
+        let Some(last_transaction) = transactions.last() else {
+            return Ok(());
+        };
+        let Some(batch_transaction_handler) = &self.batch_transaction_handler
+        else {
+            return Err(TransactionProcessorError::UnrecoverableError(
+                anyhow::anyhow!("No batch transaction handler is set"),
+            ));
+        };
+        let handled: Vec<bool> = transactions
+            .iter()
+            .map(|transaction| {
+                transaction.events.iter().any(|event| {
+                    self.handler_registry.handles_event(transaction, event)
+                })
+            })
+            .collect();
+
+        for (transaction, handler_exists) in transactions.iter().zip(&handled) {
+            if let Some(logger) = &self.logger {
+                let mut logger = logger.write().await;
+                logger
+                    .receive_transaction(transaction, *handler_exists, false)
+                    .await;
+                if !handler_exists {
+                    logger.finish_transaction(transaction, false).await;
+                }
+            }
+        }
+        // Only call the handler when there is something to handle.
+        if !handled.contains(&true) {
+            return self
+                .commit_transaction(last_transaction, false, Vec::new())
+                .await;
+        }
+
+        let snapshot =
+            self.clone_state.map(|clone_state| clone_state(&self.state));
+        let dead_letters = std::sync::Mutex::new(Vec::new());
+        let mut failed_attempts = 0;
+        loop {
+            let event_processors: Vec<_> = transactions
+                .iter()
+                .map(|transaction| EventProcessor {
+                    event_retry_policy: &self.event_retry_policy,
+                    transaction,
+                    logger: &self.logger,
+                    dead_letter_sink: &self.dead_letter_sink,
+                    dead_letters: &dead_letters,
+                    snapshot_state: self.snapshot_state,
+                    only_event_indices: None,
+                })
+                .collect();
+            let result = batch_transaction_handler
+                .handle_batch(BatchTransactionHandlerContext {
+                    state: &mut self.state,
+                    transactions,
+                    event_processors: &event_processors,
+                    handler_registry: &mut self.handler_registry,
+                })
+                .await;
+            let e = match result {
+                Ok(()) => break,
+                Err(TransactionHandlerError::TransactionRetryError(e)) => {
+                    failed_attempts += 1;
+                    if self
+                        .transaction_retry_policy
+                        .should_retry(failed_attempts)
+                    {
+                        let delay = self
+                            .transaction_retry_policy
+                            .delay(failed_attempts);
+                        // Retries are logged for the last transaction, which
+                        // is the one the batch is committed at.
+                        if let Some(logger) = &self.logger {
+                            logger
+                                .write()
+                                .await
+                                .transaction_retry_error(
+                                    last_transaction,
+                                    &e,
+                                    delay,
+                                )
+                                .await;
+                        }
+                        if let (Some(clone_state), Some(snapshot)) =
+                            (self.clone_state, &snapshot)
+                        {
+                            self.state = clone_state(snapshot);
+                        }
+                        dead_letters.lock().unwrap().clear();
+                        tokio::time::sleep(delay).await;
+                        continue;
+                    }
                     if let Some(logger) = &self.logger {
                         logger
                             .write()
                             .await
-                            .receive_transaction(
-                                transaction,
-                                handler_exists,
-                                true,
+                            .transaction_retries_exhausted(
+                                last_transaction,
+                                &e,
+                                failed_attempts,
                             )
                             .await;
                     }
-                    continue;
-                }
-                TransactionHandlerError::UnrecoverableError(e) => {
-                    if let Some(logger) = &self.logger {
-                        logger.write().await.unrecoverable_error(&e).await;
-                    }
-                    return Err(TransactionProcessorError::UnrecoverableError(
-                        e,
-                    ));
+                    e.context(format!(
+                        "Batch of transactions {} to {} failed after {} attempts",
+                        transactions[0].state_version,
+                        last_transaction.state_version,
+                        failed_attempts
+                    ))
                 }
+                Err(TransactionHandlerError::UnrecoverableError(e)) => e,
+            };
+            if let Some(logger) = &self.logger {
+                logger.write().await.unrecoverable_error(&e).await;
             }
+            if let Some(snapshot) = snapshot {
+                self.state = snapshot;
+            }
+            return Err(TransactionProcessorError::UnrecoverableError(e));
         }
         if let Some(logger) = &self.logger {
-            logger
-                .write()
-                .await
-                .finish_transaction(transaction, true)
-                .await;
+            let mut logger = logger.write().await;
+            for (transaction, handler_exists) in
+                transactions.iter().zip(&handled)
+            {
+                if *handler_exists {
+                    logger.finish_transaction(transaction, true).await;
+                }
+            }
         }
-        Ok(())
+        self.commit_transaction(
+            last_transaction,
+            true,
+            dead_letters.into_inner().unwrap(),
+        )
+        .await
     }
 
     pub async fn process_transactions(
@@ -389,6 +1160,86 @@ impl<STATE: State> TransactionProcessor<STATE> {
         }
         Ok(())
     }
+
+    /// Feeds the events parked in the [`DeadLetterSink`] through the handler that failed
+    /// to handle them again, with a single attempt each. Events that are handled
+    /// successfully are removed from the sink. The others stay parked, as do events
+    /// whose handler, identified by its [key][HandlerRegistry::handler_key],
+    /// no longer handles them.
+    ///
+    /// The event handlers are called directly with `transaction_context`, so the
+    /// transaction handler doesn't run again. Pass `&mut ()` if the handlers
+    /// don't use a transaction context.
+    ///
+    /// Returns the number of events that were handled successfully.
+    pub async fn replay_dead_letters<TRANSACTION_CONTEXT: 'static>(
+        &mut self,
+        transaction_context: &mut TRANSACTION_CONTEXT,
+    ) -> Result<usize, TransactionProcessorError> {
+        let Some(dead_letter_sink) = self.dead_letter_sink.clone() else {
+            return Ok(0);
+        };
+        let parked = dead_letter_sink
+            .lock()
+            .await
+            .list()
+            .await
+            .map_err(TransactionProcessorError::UnrecoverableError)?;
+        let mut replayed = 0;
+        for ParkedDeadLetter { id, dead_letter } in parked {
+            let Some(event_handler) = self
+                .handler_registry
+                .resolve_handler_by_key::<STATE, TRANSACTION_CONTEXT>(
+                    &dead_letter.event,
+                    &dead_letter.handler_key,
+                )
+                .cloned()
+            else {
+                log::warn!(
+                    "Handler {} doesn't handle dead letter {} ({}) anymore, keeping it parked",
+                    dead_letter.handler_key,
+                    id,
+                    dead_letter.event.name
+                );
+                continue;
+            };
+            let snapshot =
+                self.clone_state.map(|clone_state| clone_state(&self.state));
+            let result = event_handler
+                .handle(
+                    EventHandlerContext {
+                        state: &mut self.state,
+                        transaction: &dead_letter.transaction,
+                        event: &dead_letter.event,
+                        handler_registry: &mut self.handler_registry,
+                        transaction_context,
+                        event_index: dead_letter.event_index,
+                    },
+                    &dead_letter.event.binary_sbor_data,
+                )
+                .await;
+            match result {
+                Ok(()) => {
+                    dead_letter_sink.lock().await.remove(id).await.map_err(
+                        TransactionProcessorError::UnrecoverableError,
+                    )?;
+                    replayed += 1;
+                }
+                Err(err) => {
+                    if let Some(snapshot) = snapshot {
+                        self.state = snapshot;
+                    }
+                    log::warn!(
+                        "Dead letter {} ({}) failed again, keeping it parked: {:?}",
+                        id,
+                        dead_letter.event.name,
+                        err
+                    );
+                }
+            }
+        }
+        Ok(replayed)
+    }
 }
 
 /// The [`EventProcessor`]'s only purpose is to have a convenience method to process events in a transaction.
@@ -397,9 +1248,19 @@ impl<STATE: State> TransactionProcessor<STATE> {
 /// It handles retries for events that fail to process, and calls logging hooks.
 /// It is highly recommended to use this method when implementing a custom [`TransactionHandler`].
 pub struct EventProcessor<'a> {
-    event_retry_interval: Duration,
+    event_retry_policy: &'a RetryPolicy,
     transaction: &'a Transaction,
     logger: &'a Option<Arc<RwLock<Box<dyn Logger>>>>,
+    dead_letter_sink: &'a Option<Arc<Mutex<Box<dyn DeadLetterSink>>>>,
+    /// Events that failed, which are parked once the transaction is committed.
+    dead_letters: &'a std::sync::Mutex<Vec<PendingDeadLetter>>,
+    /// When set, the state is restored to a snapshot from before
+    /// a handler, when the event is parked for that handler.
+    snapshot_state: Option<SnapshotState>,
+    /// When set, only the events at these indices are processed.
+    /// This is used when the events of a transaction
+    /// are split over multiple processors.
+    only_event_indices: Option<&'a [u16]>,
 }
 
 #[allow(non_camel_case_types)]
@@ -411,7 +1272,13 @@ impl<'a> EventProcessor<'a> {
         transaction_context: &mut TRANSACTION_CONTEXT,
     ) -> Result<(), EventHandlerError> {
         for (event_index, event) in self.transaction.events.iter().enumerate() {
-            let handler_exists = handler_registry.handler_exists(event);
+            if self.only_event_indices.is_some_and(|only_event_indices| {
+                !only_event_indices.contains(&(event_index as u16))
+            }) {
+                continue;
+            }
+            let handler_exists =
+                handler_registry.handles_event(self.transaction, event);
             if !handler_exists {
                 continue;
             }
@@ -427,74 +1294,148 @@ impl<'a> EventProcessor<'a> {
                     )
                     .await;
             }
-            let event_handler = {
-                handler_registry
-                    .handler::<STATE, TRANSACTION_CONTEXT>(
-                        event.emitter.address(),
-                        &event.name,
+            let event_handlers: Vec<_> = handler_registry
+                .resolve_handlers::<STATE, TRANSACTION_CONTEXT>(event)
+                .into_iter()
+                .map(|(handler_id, event_handler)| {
+                    (handler_id, event_handler.clone())
+                })
+                .collect();
+            // A retry policy set on the matcher of the handlers takes
+            // precedence over the processor-wide event retry policy.
+            let retry_policy = handler_registry
+                .retry_policy(event)
+                .unwrap_or(self.event_retry_policy)
+                .clone();
+            'handlers: for (handler_id, event_handler) in event_handlers {
+                if let Some(logger) = self.logger {
+                    logger
+                        .write()
+                        .await
+                        .receive_event_handler(
+                            self.transaction,
+                            event,
+                            handler_id,
+                            false,
+                        )
+                        .await;
+                }
+                // Take a snapshot of the state, so the changes of a handler
+                // whose event is parked can be undone, if rollbacks are enabled.
+                let snapshot = self
+                    .snapshot_state
+                    .filter(|_| self.dead_letter_sink.is_some())
+                    .map(|snapshot_state| snapshot_state(&*state));
+                let mut failed_attempts = 0;
+                while let Err(err) = event_handler
+                    .handle(
+                        EventHandlerContext {
+                            state,
+                            transaction: self.transaction,
+                            event,
+                            handler_registry,
+                            transaction_context,
+                            event_index: event_index as u16,
+                        },
+                        &event.binary_sbor_data,
                     )
-                    .or_else(|| {
-                        let entity_type = match &event.emitter {
-                            EventEmitter::Method { entity_type, .. } => {
-                                entity_type
-                            }
-                            EventEmitter::Function { .. } => {
-                                panic!("Got a function call while expecting a native event.")
+                    .await
+                {
+                    let (e, attempts) = match err {
+                        EventHandlerError::EventRetryError(e) => {
+                            failed_attempts += 1;
+                            if !retry_policy.should_retry(failed_attempts) {
+                                // The retry policy gave up on this event, so park it
+                                // if possible, or escalate to a transaction retry.
+                                if let Some(logger) = self.logger {
+                                    logger
+                                        .write()
+                                        .await
+                                        .event_retries_exhausted(
+                                            self.transaction,
+                                            event,
+                                            &e,
+                                            failed_attempts,
+                                        )
+                                        .await;
+                                }
+                                if self.dead_letter_sink.is_none() {
+                                    return Err(
+                                        EventHandlerError::TransactionRetryError(
+                                            e,
+                                        ),
+                                    );
+                                }
+                                (e, failed_attempts)
+                            } else {
+                                let delay = retry_policy.delay(failed_attempts);
+                                if let Some(logger) = self.logger {
+                                    logger
+                                        .write()
+                                        .await
+                                        .event_retry_error(
+                                            self.transaction,
+                                            event,
+                                            &e,
+                                            delay,
+                                        )
+                                        .await;
+                                }
+                                tokio::time::sleep(delay).await;
+                                if let Some(logger) = self.logger {
+                                    let mut logger = logger.write().await;
+                                    logger
+                                        .receive_event(
+                                            self.transaction,
+                                            event,
+                                            handler_exists,
+                                            true,
+                                        )
+                                        .await;
+                                    logger
+                                        .receive_event_handler(
+                                            self.transaction,
+                                            event,
+                                            handler_id,
+                                            true,
+                                        )
+                                        .await;
+                                }
+                                continue;
                             }
-                        };
-                        handler_registry.native_handler(
-                            NativeEventType::resolve(&event.name, entity_type.clone()).unwrap(),
-                        )
-                    })
-                    .unwrap()
-            };
-            let event_handler = event_handler.clone();
-            while let Err(err) = event_handler
-                .handle(
-                    EventHandlerContext {
-                        state,
-                        transaction: self.transaction,
-                        event,
-                        handler_registry,
-                        transaction_context,
-                        event_index: event_index as u16,
-                    },
-                    &event.binary_sbor_data,
-                )
-                .await
-            {
-                match err {
-                    EventHandlerError::EventRetryError(e) => {
-                        if let Some(logger) = self.logger {
-                            logger
-                                .write()
-                                .await
-                                .event_retry_error(
-                                    self.transaction,
-                                    event,
-                                    &e,
-                                    self.event_retry_interval,
-                                )
-                                .await;
                         }
-                        tokio::time::sleep(self.event_retry_interval).await;
-                        if let Some(logger) = self.logger {
-                            logger
-                                .write()
-                                .await
-                                .receive_event(
-                                    self.transaction,
-                                    event,
-                                    handler_exists,
-                                    true,
-                                )
-                                .await;
+                        EventHandlerError::UnrecoverableError(e)
+                            if self.dead_letter_sink.is_some() =>
+                        {
+                            (e, failed_attempts + 1)
                         }
-                        continue;
-                    }
-                    _ => {
-                        return Err(err);
+                        _ => {
+                            return Err(err);
+                        }
+                    };
+                    // Park the event for this handler
+                    // and continue with the next handler.
+                    if let Some(snapshot) = snapshot {
+                        *state = *snapshot.downcast::<STATE>().expect(
+                            "Snapshot has a different type than the state",
+                        );
                     }
+                    let handler_key = handler_registry
+                        .handler_key(handler_id)
+                        .unwrap_or_default();
+                    self.park_event(event_index, handler_key, e, attempts)?;
+                    continue 'handlers;
+                }
+                if let Some(logger) = self.logger {
+                    logger
+                        .write()
+                        .await
+                        .finish_event_handler(
+                            self.transaction,
+                            event,
+                            handler_id,
+                        )
+                        .await;
                 }
             }
             if let Some(logger) = self.logger {
@@ -507,4 +1448,52 @@ impl<'a> EventProcessor<'a> {
         }
         Ok(())
     }
+
+    /// Keeps a failed event to be parked in the [`DeadLetterSink`] once
+    /// the transaction is committed. Without a sink, the error is returned.
+    fn park_event(
+        &self,
+        event_index: usize,
+        handler_key: &str,
+        error: anyhow::Error,
+        attempts: u32,
+    ) -> Result<(), EventHandlerError> {
+        if self.dead_letter_sink.is_none() {
+            return Err(EventHandlerError::UnrecoverableError(error));
+        }
+        let dead_letter = DeadLetter::new(
+            self.transaction,
+            event_index as u16,
+            handler_key,
+            &error,
+            attempts,
+        );
+        self.dead_letters
+            .lock()
+            .unwrap()
+            .push(PendingDeadLetter { dead_letter, error });
+        Ok(())
+    }
+}
+
+/// A failed event that is parked once its transaction is committed,
+/// together with the error, which is passed to the logger.
+struct PendingDeadLetter {
+    dead_letter: DeadLetter,
+    error: anyhow::Error,
+}
+
+/// Clones a state behind [`Any`], so the [`EventProcessor`]
+/// can take snapshots without knowing the type of the state.
+type SnapshotState = fn(&dyn Any) -> Box<dyn Any + Send>;
+
+fn snapshot_state<STATE: State + Clone>(
+    state: &dyn Any,
+) -> Box<dyn Any + Send> {
+    Box::new(
+        state
+            .downcast_ref::<STATE>()
+            .expect("Snapshot has a different type than the state")
+            .clone(),
+    )
 }
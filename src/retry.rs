@@ -0,0 +1,103 @@
+//! Retry policies that determine how long to wait before retrying
+//! a failed event or transaction, and when to give up.
+//!
+//! A [`RetryPolicy`] can be set globally on the
+//! [`TransactionStreamProcessor`][crate::processor::TransactionStreamProcessor]
+//! for events and transactions, and per event handler on the
+//! [`HandlerRegistry`][crate::event_handler::HandlerRegistry].
+//!
+//! When an event runs out of attempts, the failure is escalated to a
+//! transaction retry, or the event is parked when a
+//! [`DeadLetterSink`][crate::dead_letters::DeadLetterSink] is set. When a transaction runs out of attempts,
+//! the processor stops with an unrecoverable error.
+
+use rand::Rng;
+use std::time::Duration;
+
+/// Describes the delay between consecutive attempts and the maximum
+/// number of attempts. Delays grow exponentially by `multiplier` from
+/// `initial_delay` up to `max_delay`, and can be randomized with `jitter`
+/// so that many failing clients don't retry at the exact same time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetryPolicy {
+    initial_delay: Duration,
+    max_delay: Duration,
+    multiplier: f64,
+    jitter: f64,
+    max_attempts: Option<u32>,
+}
+
+impl Default for RetryPolicy {
+    /// Retries forever, every 10 seconds.
+    fn default() -> Self {
+        Self::fixed(Duration::from_secs(10))
+    }
+}
+
+impl RetryPolicy {
+    /// Creates a policy that retries forever with the same delay in between.
+    pub fn fixed(delay: Duration) -> Self {
+        Self {
+            initial_delay: delay,
+            max_delay: delay,
+            multiplier: 1.0,
+            jitter: 0.0,
+            max_attempts: None,
+        }
+    }
+
+    /// Creates a policy that retries forever, doubling the delay
+    /// after each attempt until it reaches `max_delay`.
+    pub fn exponential(initial_delay: Duration, max_delay: Duration) -> Self {
+        Self {
+            initial_delay,
+            max_delay,
+            multiplier: 2.0,
+            jitter: 0.0,
+            max_attempts: None,
+        }
+    }
+
+    /// Sets the factor by which the delay grows after each attempt.
+    pub fn multiplier(mut self, multiplier: f64) -> Self {
+        self.multiplier = multiplier.max(1.0);
+        self
+    }
+
+    /// Sets the fraction of the delay that is randomized, between 0 and 1.
+    /// With a jitter of 0.5, a delay of 10 seconds becomes a random delay
+    /// between 5 and 10 seconds.
+    pub fn jitter(mut self, jitter: f64) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    /// Sets the maximum number of attempts, including the first one.
+    /// After this many failed attempts, no more retries are done.
+    pub fn max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = Some(max_attempts.max(1));
+        self
+    }
+
+    /// Returns whether another attempt should be made after
+    /// `failed_attempts` attempts have failed.
+    pub fn should_retry(&self, failed_attempts: u32) -> bool {
+        self.max_attempts
+            .map_or(true, |max_attempts| failed_attempts < max_attempts)
+    }
+
+    /// Returns the delay to wait after `failed_attempts` attempts have failed.
+    pub fn delay(&self, failed_attempts: u32) -> Duration {
+        let exponent = failed_attempts.saturating_sub(1).min(i32::MAX as u32);
+        let delay = self.initial_delay.as_secs_f64()
+            * self.multiplier.powi(exponent as i32);
+        let delay = delay.min(self.max_delay.as_secs_f64());
+        let delay = if self.jitter > 0.0 {
+            delay * rand::thread_rng().gen_range(1.0 - self.jitter..=1.0)
+        } else {
+            delay
+        };
+        // Near `Duration::MAX`, rounding can take the delay out of range.
+        Duration::try_from_secs_f64(delay).unwrap_or(self.max_delay)
+    }
+}
@@ -0,0 +1,262 @@
+//! A compact binary archive format for transactions, which the
+//! [`FileTransactionStream`][crate::sources::file::FileTransactionStream]
+//! reads natively and the [`RecordingStream`][crate::sources::recording::RecordingStream]
+//! writes.
+//!
+//! An archive (`.bin`) starts with a 4 byte magic value and a version
+//! byte, followed by the transactions in order of state version. Each
+//! transaction is a little-endian `u32` length, followed by that many bytes
+//! of a bincode-encoded [`Transaction`], which keeps the binary SBOR data
+//! of events as is, so it doesn't have to be encoded again on replay.
+//!
+//! An uncompressed archive can have a sparse index next to it, with the
+//! same file name plus `.idx`. The index holds the state version and byte
+//! offset of every [`INDEX_INTERVAL`]th transaction, as pairs of
+//! little-endian `u64`s, so a reader can seek close to any state version
+//! instead of decoding the whole archive.
+
+use crate::models::{
+    Event, EventEmitter, Transaction, TransactionKind, TransactionStatus,
+};
+use serde::{Deserialize, Serialize};
+use std::{
+    io::{BufRead, Read, Write},
+    path::{Path, PathBuf},
+};
+
+const MAGIC: [u8; 4] = *b"RXTA";
+const VERSION: u8 = 1;
+
+/// The length of the header at the start of an archive.
+pub(crate) const HEADER_LENGTH: u64 = MAGIC.len() as u64 + 1;
+
+/// The length of the `u32` that precedes each record.
+const LENGTH_PREFIX_LENGTH: usize = 4;
+
+/// The length of an entry in the index.
+pub(crate) const INDEX_ENTRY_LENGTH: u64 = 16;
+
+/// The number of transactions between entries in the index.
+pub(crate) const INDEX_INTERVAL: u64 = 256;
+
+/// A [`Transaction`] in a form that bincode can encode. The JSON
+/// of events and the timestamp can't be decoded by bincode directly.
+#[derive(Serialize, Deserialize)]
+struct ArchivedTransaction {
+    intent_hash: String,
+    state_version: u64,
+    kind: TransactionKind,
+    status: TransactionStatus,
+    error_message: Option<String>,
+    /// Seconds and nanoseconds since the Unix epoch.
+    confirmed_at: Option<(i64, u32)>,
+    events: Vec<ArchivedEvent>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ArchivedEvent {
+    name: String,
+    binary_sbor_data: Vec<u8>,
+    json_sbor_data: Option<String>,
+    emitter: EventEmitter,
+}
+
+impl From<Transaction> for ArchivedTransaction {
+    fn from(transaction: Transaction) -> Self {
+        Self {
+            intent_hash: transaction.intent_hash,
+            state_version: transaction.state_version,
+            kind: transaction.kind,
+            status: transaction.status,
+            error_message: transaction.error_message,
+            confirmed_at: transaction.confirmed_at.map(|confirmed_at| {
+                (
+                    confirmed_at.timestamp(),
+                    confirmed_at.timestamp_subsec_nanos(),
+                )
+            }),
+            events: transaction
+                .events
+                .into_iter()
+                .map(|event| ArchivedEvent {
+                    name: event.name,
+                    binary_sbor_data: event.binary_sbor_data,
+                    json_sbor_data: event
+                        .json_sbor_data
+                        .map(|json| json.to_string()),
+                    emitter: event.emitter,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl TryFrom<ArchivedTransaction> for Transaction {
+    type Error = anyhow::Error;
+
+    fn try_from(
+        transaction: ArchivedTransaction,
+    ) -> Result<Self, anyhow::Error> {
+        let confirmed_at = transaction
+            .confirmed_at
+            .map(|(seconds, nanoseconds)| {
+                chrono::DateTime::from_timestamp(seconds, nanoseconds)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid timestamp"))
+            })
+            .transpose()?;
+        let events = transaction
+            .events
+            .into_iter()
+            .map(|event| {
+                Ok(Event {
+                    name: event.name,
+                    binary_sbor_data: event.binary_sbor_data,
+                    json_sbor_data: event
+                        .json_sbor_data
+                        .map(|json| serde_json::from_str(&json))
+                        .transpose()?,
+                    emitter: event.emitter,
+                })
+            })
+            .collect::<Result<_, anyhow::Error>>()?;
+        Ok(Transaction {
+            intent_hash: transaction.intent_hash,
+            state_version: transaction.state_version,
+            kind: transaction.kind,
+            status: transaction.status,
+            error_message: transaction.error_message,
+            confirmed_at,
+            events,
+        })
+    }
+}
+
+pub(crate) fn write_header(writer: &mut dyn Write) -> std::io::Result<()> {
+    writer.write_all(&MAGIC)?;
+    writer.write_all(&[VERSION])
+}
+
+/// Checks that the reader is at the start of an archive
+/// with a version that can be read.
+pub(crate) fn read_header(reader: &mut impl Read) -> Result<(), anyhow::Error> {
+    let mut header = [0; HEADER_LENGTH as usize];
+    reader.read_exact(&mut header)?;
+    if header[..MAGIC.len()] != MAGIC {
+        return Err(anyhow::anyhow!("Not a transaction archive"));
+    }
+    if header[MAGIC.len()] != VERSION {
+        return Err(anyhow::anyhow!(
+            "Unsupported archive version {}",
+            header[MAGIC.len()]
+        ));
+    }
+    Ok(())
+}
+
+/// Encodes a transaction as a length-prefixed record.
+pub(crate) fn encode_transaction(
+    transaction: Transaction,
+) -> Result<Vec<u8>, anyhow::Error> {
+    let encoded = bincode::serialize(&ArchivedTransaction::from(transaction))?;
+    let length = u32::try_from(encoded.len())?;
+    let mut record = Vec::with_capacity(LENGTH_PREFIX_LENGTH + encoded.len());
+    record.extend_from_slice(&length.to_le_bytes());
+    record.extend_from_slice(&encoded);
+    Ok(record)
+}
+
+/// Reads the next record, or returns `None` at the end of the archive.
+pub(crate) fn read_transaction(
+    reader: &mut impl BufRead,
+) -> Result<Option<Transaction>, anyhow::Error> {
+    read_record(reader)?
+        .map(|encoded| decode_record(&encoded))
+        .transpose()
+}
+
+/// Reads the next record without decoding it, or returns `None` at the
+/// end of the archive. Fails with [`std::io::ErrorKind::UnexpectedEof`]
+/// when the archive ends partway through the record.
+pub(crate) fn read_record(
+    reader: &mut impl BufRead,
+) -> std::io::Result<Option<Vec<u8>>> {
+    if reader.fill_buf()?.is_empty() {
+        return Ok(None);
+    }
+    let mut length = [0; LENGTH_PREFIX_LENGTH];
+    reader.read_exact(&mut length)?;
+    let mut encoded = vec![0; u32::from_le_bytes(length) as usize];
+    reader.read_exact(&mut encoded)?;
+    Ok(Some(encoded))
+}
+
+/// The length of a record in the archive, including its length prefix.
+pub(crate) fn record_length(encoded: &[u8]) -> u64 {
+    (LENGTH_PREFIX_LENGTH + encoded.len()) as u64
+}
+
+/// Decodes a record that was read with [`read_record`].
+pub(crate) fn decode_record(
+    encoded: &[u8],
+) -> Result<Transaction, anyhow::Error> {
+    let transaction: ArchivedTransaction = bincode::deserialize(encoded)?;
+    transaction.try_into()
+}
+
+/// The path of the index of the archive at `path`.
+pub(crate) fn index_path(path: &Path) -> PathBuf {
+    let mut index_path = path.to_path_buf().into_os_string();
+    index_path.push(".idx");
+    index_path.into()
+}
+
+/// An entry in the index of an archive.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct IndexEntry {
+    pub(crate) state_version: u64,
+    pub(crate) offset: u64,
+}
+
+impl IndexEntry {
+    pub(crate) fn to_bytes(self) -> [u8; 16] {
+        let mut bytes = [0; 16];
+        bytes[..8].copy_from_slice(&self.state_version.to_le_bytes());
+        bytes[8..].copy_from_slice(&self.offset.to_le_bytes());
+        bytes
+    }
+}
+
+/// Reads the index of the archive at `path`, if it has one.
+pub(crate) fn read_index(
+    path: &Path,
+) -> Result<Option<Vec<IndexEntry>>, anyhow::Error> {
+    let bytes = match std::fs::read(index_path(path)) {
+        Ok(bytes) => bytes,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+            return Ok(None)
+        }
+        Err(err) => return Err(err.into()),
+    };
+    // An entry that was cut off while writing is ignored.
+    let entries = bytes
+        .chunks_exact(INDEX_ENTRY_LENGTH as usize)
+        .map(|chunk| IndexEntry {
+            state_version: u64::from_le_bytes(chunk[..8].try_into().unwrap()),
+            offset: u64::from_le_bytes(chunk[8..].try_into().unwrap()),
+        })
+        .collect();
+    Ok(Some(entries))
+}
+
+/// Finds the offset of the last indexed transaction at or before
+/// `state_version`, from where a reader can continue.
+pub(crate) fn seek_offset(
+    index: &[IndexEntry],
+    state_version: u64,
+) -> Option<u64> {
+    let position =
+        index.partition_point(|entry| entry.state_version <= state_version);
+    position
+        .checked_sub(1)
+        .map(|position| index[position].offset)
+}
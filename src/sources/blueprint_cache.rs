@@ -0,0 +1,83 @@
+//! A cache of the blueprints that entities were instantiated from,
+//! shared by the sources that resolve them.
+
+use crate::models::{BlueprintId, EventEmitter, Transaction};
+use std::collections::{HashMap, HashSet};
+
+/// Maps entity addresses to their blueprint. Entities without a known
+/// blueprint are cached as `None`, so they aren't looked up again.
+/// The cache is cleared when it would grow beyond its capacity.
+#[derive(Debug)]
+pub(crate) struct BlueprintCache {
+    blueprints: HashMap<String, Option<BlueprintId>>,
+    capacity: usize,
+}
+
+impl BlueprintCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            blueprints: HashMap::new(),
+            capacity,
+        }
+    }
+
+    /// Returns the addresses of the method emitters in the transactions
+    /// which are not cached yet, optionally only the global ones.
+    pub fn unknown_addresses(
+        &self,
+        transactions: &[Transaction],
+        only_global: bool,
+    ) -> Vec<String> {
+        let mut addresses = HashSet::new();
+        for event in transactions.iter().flat_map(|tx| tx.events.iter()) {
+            if let EventEmitter::Method {
+                entity_address,
+                is_global,
+                ..
+            } = &event.emitter
+            {
+                if (*is_global || !only_global)
+                    && !self.blueprints.contains_key(entity_address)
+                {
+                    addresses.insert(entity_address.clone());
+                }
+            }
+        }
+        addresses.into_iter().collect()
+    }
+
+    /// Adds the blueprints of the given addresses. Addresses
+    /// that are missing from `blueprints` are cached as `None`.
+    pub fn extend(
+        &mut self,
+        addresses: Vec<String>,
+        mut blueprints: HashMap<String, BlueprintId>,
+    ) {
+        if self.blueprints.len() + addresses.len() > self.capacity {
+            self.blueprints.clear();
+        }
+        for address in addresses {
+            let blueprint = blueprints.remove(&address);
+            self.blueprints.insert(address, blueprint);
+        }
+    }
+
+    /// Sets the blueprint of all method emitters in the transactions
+    /// for which it is cached.
+    pub fn attach(&self, transactions: &mut [Transaction]) {
+        for event in transactions.iter_mut().flat_map(|tx| tx.events.iter_mut())
+        {
+            if let EventEmitter::Method {
+                entity_address,
+                blueprint,
+                ..
+            } = &mut event.emitter
+            {
+                if let Some(Some(cached)) = self.blueprints.get(entity_address)
+                {
+                    *blueprint = Some(cached.clone());
+                }
+            }
+        }
+    }
+}
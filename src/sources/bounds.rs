@@ -0,0 +1,56 @@
+//! The end of a bounded stream, shared by the sources that support it.
+
+use crate::models::Transaction;
+use chrono::{DateTime, Utc};
+
+/// Where a source should stop streaming. Without any
+/// bounds set, a source streams forever.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct StreamBounds {
+    /// The last state version to stream, inclusive.
+    pub to_state_version: Option<u64>,
+    /// Transactions confirmed at or after this time are not streamed.
+    pub until_timestamp: Option<DateTime<Utc>>,
+}
+
+impl StreamBounds {
+    /// Returns whether the transaction is within the bounds.
+    pub fn contains(&self, transaction: &Transaction) -> bool {
+        self.to_state_version
+            .map_or(true, |to| transaction.state_version <= to)
+            && self
+                .until_timestamp
+                .zip(transaction.confirmed_at)
+                .map_or(true, |(until, confirmed_at)| confirmed_at < until)
+    }
+
+    /// Returns whether the transaction is the last one that
+    /// can be within the bounds.
+    pub fn is_last(&self, transaction: &Transaction) -> bool {
+        self.to_state_version
+            .is_some_and(|to| transaction.state_version >= to)
+    }
+
+    /// Returns whether a ledger which is at the given state version and
+    /// timestamp has passed the bounds, so that a source which is caught up
+    /// with it won't receive any more transactions within the bounds.
+    pub fn is_passed_by(
+        &self,
+        state_version: u64,
+        timestamp: DateTime<Utc>,
+    ) -> bool {
+        self.to_state_version.is_some_and(|to| state_version >= to)
+            || self.until_timestamp.is_some_and(|until| timestamp >= until)
+    }
+
+    /// Returns the state version that a source which is caught up with a
+    /// ledger at `state_version` has reached within the bounds.
+    pub fn reached_at(&self, state_version: u64) -> u64 {
+        self.to_state_version
+            .map_or(state_version, |to| to.min(state_version))
+    }
+
+    pub fn is_bounded(&self) -> bool {
+        self.to_state_version.is_some() || self.until_timestamp.is_some()
+    }
+}
@@ -0,0 +1,438 @@
+//! A source that fans out the transactions of one stream to multiple
+//! subscriber streams, so several independent processors can consume
+//! the same ledger feed.
+
+use crate::{logger::Logger, models::Transaction, stream::TransactionStream};
+use async_trait::async_trait;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+use tokio::sync::{
+    mpsc::{error::TrySendError, Receiver, Sender},
+    RwLock,
+};
+
+/// What happens when a subscriber's buffer is full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LagPolicy {
+    /// Wait until the subscriber has room, which holds back all other
+    /// subscribers as well. No subscriber misses a transaction.
+    #[default]
+    Block,
+    /// Drop the transaction for that subscriber and carry on. Dropped
+    /// transactions are logged and counted, see
+    /// [`BroadcastTransactionStream::dropped_transactions`].
+    Drop,
+}
+
+/// Fans out the transactions of an upstream stream to any number of
+/// subscribers. Each subscriber is a [`BroadcastTransactionStream`] with
+/// its own buffer, which can be given to its own
+/// [`TransactionStreamProcessor`][crate::processor::TransactionStreamProcessor].
+///
+/// ```ignore
+/// let broadcast = TransactionBroadcast::new(gateway_stream)
+///     .lag_policy(LagPolicy::Block);
+/// let pools_stream = broadcast.subscribe();
+/// let tokens_stream = broadcast.subscribe();
+/// ```
+///
+/// The upstream stream is started once every subscriber has been started,
+/// or stopped without being started, so none of them miss the first
+/// transactions. When every subscriber
+/// resumes from a checkpoint, the upstream stream resumes from the lowest
+/// one, and each subscriber skips the transactions before its own.
+/// Otherwise, the upstream stream starts where it was configured to.
+/// Subscribing after that joins the live stream.
+///
+/// The upstream stream is stopped once every subscriber has stopped.
+/// When it gives up on its source, every subscriber fails with its error.
+#[derive(Debug)]
+pub struct TransactionBroadcast<S> {
+    shared: Arc<Shared<S>>,
+}
+
+#[derive(Debug)]
+struct Shared<S> {
+    upstream: tokio::sync::Mutex<S>,
+    hub: std::sync::Mutex<Hub>,
+}
+
+#[derive(Debug)]
+struct Hub {
+    subscribers: Vec<Subscriber>,
+    buffer_capacity: usize,
+    lag_policy: LagPolicy,
+    started: bool,
+    finished: bool,
+    /// The error the upstream stream gave up on, shared by the subscribers.
+    error: Option<Arc<anyhow::Error>>,
+    join_handle: Option<tokio::task::JoinHandle<()>>,
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    state: SubscriberState,
+    from_state_version: Option<u64>,
+    dropped: Arc<AtomicU64>,
+}
+
+#[derive(Debug)]
+enum SubscriberState {
+    Waiting,
+    Started(Sender<Transaction>),
+    Stopped,
+}
+
+impl Hub {
+    fn is_active(&self) -> bool {
+        self.subscribers.iter().any(|subscriber| {
+            !matches!(subscriber.state, SubscriberState::Stopped)
+        })
+    }
+
+    fn is_ready(&self) -> bool {
+        self.subscribers.iter().all(|subscriber| {
+            !matches!(subscriber.state, SubscriberState::Waiting)
+        })
+    }
+
+    /// The state version to resume the upstream stream from, if every
+    /// subscriber that takes part resumes from a checkpoint.
+    fn resume_state_version(&self) -> Option<u64> {
+        let mut resume_state_version: Option<u64> = None;
+        for subscriber in &self.subscribers {
+            if let SubscriberState::Started(_) = subscriber.state {
+                let state_version = subscriber.from_state_version?;
+                resume_state_version = Some(
+                    resume_state_version
+                        .map_or(state_version, |min| min.min(state_version)),
+                );
+            }
+        }
+        resume_state_version
+    }
+}
+
+impl<S: TransactionStream + Send + 'static> TransactionBroadcast<S> {
+    /// Creates a broadcast of the transactions of `upstream`.
+    pub fn new(upstream: S) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                upstream: tokio::sync::Mutex::new(upstream),
+                hub: std::sync::Mutex::new(Hub {
+                    subscribers: Vec::new(),
+                    buffer_capacity: 1000,
+                    lag_policy: LagPolicy::default(),
+                    started: false,
+                    finished: false,
+                    error: None,
+                    join_handle: None,
+                }),
+            }),
+        }
+    }
+
+    /// Sets the buffer capacity of each subscriber.
+    pub fn buffer_capacity(self, buffer_capacity: u64) -> Self {
+        self.shared.hub.lock().unwrap().buffer_capacity =
+            buffer_capacity as usize;
+        self
+    }
+
+    /// Sets what happens when a subscriber's buffer is full.
+    /// Defaults to [`LagPolicy::Block`].
+    pub fn lag_policy(self, lag_policy: LagPolicy) -> Self {
+        self.shared.hub.lock().unwrap().lag_policy = lag_policy;
+        self
+    }
+
+    /// Creates a new subscriber stream. It receives every transaction
+    /// from the moment the upstream stream is started, or, when the
+    /// upstream stream is already running, from the moment it is started
+    /// itself.
+    pub fn subscribe(&self) -> BroadcastTransactionStream<S> {
+        let mut hub = self.shared.hub.lock().unwrap();
+        let dropped = Arc::new(AtomicU64::new(0));
+        hub.subscribers.push(Subscriber {
+            state: SubscriberState::Waiting,
+            from_state_version: None,
+            dropped: dropped.clone(),
+        });
+        BroadcastTransactionStream {
+            shared: self.shared.clone(),
+            id: hub.subscribers.len() - 1,
+            dropped,
+        }
+    }
+}
+
+/// A subscriber of a [`TransactionBroadcast`].
+///
+/// Dropping a subscriber without stopping it, for example when its
+/// processor is never run, stops it in the background, so the other
+/// subscribers don't wait for it.
+#[derive(Debug)]
+pub struct BroadcastTransactionStream<S>
+where
+    S: TransactionStream + Send + 'static,
+{
+    shared: Arc<Shared<S>>,
+    id: usize,
+    dropped: Arc<AtomicU64>,
+}
+
+impl<S: TransactionStream + Send + 'static> BroadcastTransactionStream<S> {
+    /// The number of transactions this subscriber missed
+    /// because it was lagging behind, with [`LagPolicy::Drop`].
+    pub fn dropped_transactions(&self) -> u64 {
+        self.dropped.load(Ordering::Relaxed)
+    }
+}
+
+/// A subscriber to forward a transaction to.
+struct Target {
+    id: usize,
+    sender: Sender<Transaction>,
+    from_state_version: Option<u64>,
+    dropped: Arc<AtomicU64>,
+}
+
+impl<S: TransactionStream + Send + 'static> Shared<S> {
+    fn targets(&self) -> (Vec<Target>, LagPolicy) {
+        let hub = self.hub.lock().unwrap();
+        let targets = hub
+            .subscribers
+            .iter()
+            .enumerate()
+            .filter_map(|(id, subscriber)| match &subscriber.state {
+                SubscriberState::Started(sender) => Some(Target {
+                    id,
+                    sender: sender.clone(),
+                    from_state_version: subscriber.from_state_version,
+                    dropped: subscriber.dropped.clone(),
+                }),
+                _ => None,
+            })
+            .collect();
+        (targets, hub.lag_policy)
+    }
+
+    fn remove(&self, id: usize) {
+        self.hub.lock().unwrap().subscribers[id].state =
+            SubscriberState::Stopped;
+    }
+
+    /// Stops a subscriber. Returns the task that distributes the upstream
+    /// stream when it was the last subscriber to stop, after which
+    /// the upstream stream should be stopped too.
+    fn stop_subscriber(
+        &self,
+        id: usize,
+    ) -> Option<tokio::task::JoinHandle<()>> {
+        let mut hub = self.hub.lock().unwrap();
+        hub.subscribers[id].state = SubscriberState::Stopped;
+        if hub.is_active() || !hub.started {
+            None
+        } else {
+            hub.join_handle.take()
+        }
+    }
+
+    /// Deals with a subscriber that stopped: stops the upstream stream
+    /// with the task that distributes it, or otherwise starts it, as the
+    /// other subscribers may have been waiting for this one.
+    async fn release(
+        self: Arc<Self>,
+        join_handle: Option<tokio::task::JoinHandle<()>>,
+    ) {
+        match join_handle {
+            Some(join_handle) => {
+                join_handle.abort();
+                self.upstream.lock().await.stop().await;
+            }
+            None => {
+                if let Err(err) = self.clone().start_upstream().await {
+                    log::error!(
+                        "Failed to start the upstream stream of the broadcast: {:?}",
+                        err
+                    );
+                    self.finish(Some(err));
+                }
+            }
+        }
+    }
+
+    /// Ends the streams of all subscribers, with the error
+    /// that ended the upstream stream, if any.
+    fn finish(&self, error: Option<anyhow::Error>) {
+        let mut hub = self.hub.lock().unwrap();
+        hub.finished = true;
+        hub.error = error.map(Arc::new);
+        for subscriber in &mut hub.subscribers {
+            subscriber.state = SubscriberState::Stopped;
+        }
+    }
+
+    /// Starts the upstream stream once every subscriber has been started
+    /// or stopped, if it wasn't started yet and any subscriber is left.
+    async fn start_upstream(self: Arc<Self>) -> Result<(), anyhow::Error> {
+        let resume_state_version = {
+            let mut hub = self.hub.lock().unwrap();
+            if hub.started || !hub.is_ready() || !hub.is_active() {
+                return Ok(());
+            }
+            hub.started = true;
+            hub.resume_state_version()
+        };
+        let receiver = {
+            let mut upstream = self.upstream.lock().await;
+            if let Some(state_version) = resume_state_version {
+                upstream.resume_from_state_version(state_version);
+            }
+            upstream.start().await
+        };
+        let receiver = match receiver {
+            Ok(receiver) => receiver,
+            Err(err) => {
+                self.hub.lock().unwrap().started = false;
+                return Err(err);
+            }
+        };
+        let handle = tokio::spawn(self.clone().distribute(receiver));
+        self.hub.lock().unwrap().join_handle = Some(handle);
+        Ok(())
+    }
+
+    async fn distribute(self: Arc<Self>, mut receiver: Receiver<Transaction>) {
+        while let Some(transaction) = receiver.recv().await {
+            let (targets, lag_policy) = self.targets();
+            for target in targets {
+                if target.from_state_version.is_some_and(|state_version| {
+                    transaction.state_version < state_version
+                }) {
+                    continue;
+                }
+                let closed = match lag_policy {
+                    LagPolicy::Block => {
+                        target.sender.send(transaction.clone()).await.is_err()
+                    }
+                    LagPolicy::Drop => {
+                        match target.sender.try_send(transaction.clone()) {
+                            Ok(()) => false,
+                            Err(TrySendError::Full(_)) => {
+                                let dropped = target
+                                    .dropped
+                                    .fetch_add(1, Ordering::Relaxed);
+                                if dropped % 1000 == 0 {
+                                    log::warn!(
+                                        "Broadcast subscriber {} is lagging behind, dropped transaction at state version {} ({} dropped in total)",
+                                        target.id,
+                                        transaction.state_version,
+                                        dropped + 1
+                                    );
+                                }
+                                false
+                            }
+                            Err(TrySendError::Closed(_)) => true,
+                        }
+                    }
+                };
+                // The subscriber dropped its receiver.
+                if closed {
+                    self.remove(target.id);
+                }
+            }
+            if !self.hub.lock().unwrap().is_active() {
+                log::info!("All broadcast subscribers have stopped");
+                break;
+            }
+        }
+        let error = self.upstream.lock().await.take_error();
+        self.finish(error);
+        self.upstream.lock().await.stop().await;
+    }
+}
+
+#[async_trait]
+impl<S: TransactionStream + Send + 'static> TransactionStream
+    for BroadcastTransactionStream<S>
+{
+    async fn start(&mut self) -> Result<Receiver<Transaction>, anyhow::Error> {
+        let rx = {
+            let mut hub = self.shared.hub.lock().unwrap();
+            let (tx, rx) = tokio::sync::mpsc::channel(hub.buffer_capacity);
+            // The upstream stream already ended, so the channel is
+            // closed straight away by dropping the sender.
+            if !hub.finished {
+                hub.subscribers[self.id].state = SubscriberState::Started(tx);
+            }
+            rx
+        };
+        self.shared.clone().start_upstream().await?;
+        Ok(rx)
+    }
+
+    async fn stop(&mut self) {
+        let join_handle = self.shared.stop_subscriber(self.id);
+        self.shared.clone().release(join_handle).await;
+    }
+
+    fn resume_from_state_version(&mut self, state_version: u64) {
+        self.shared.hub.lock().unwrap().subscribers[self.id]
+            .from_state_version = Some(state_version);
+    }
+
+    /// Every subscriber passes on the logger of its processor, so the
+    /// upstream stream reports to the logger of the last one to start.
+    fn set_logger(&mut self, logger: Arc<RwLock<Box<dyn Logger>>>) {
+        if let Ok(mut upstream) = self.shared.upstream.try_lock() {
+            upstream.set_logger(logger);
+        }
+    }
+
+    /// Every subscriber fails with the error of the upstream stream.
+    fn take_error(&mut self) -> Option<anyhow::Error> {
+        let hub = self.shared.hub.lock().unwrap();
+        hub.error.as_ref().map(|err| {
+            anyhow::anyhow!(
+                "The upstream stream of the broadcast failed: {:?}",
+                err
+            )
+        })
+    }
+}
+
+impl<S: TransactionStream + Send + 'static> Drop
+    for BroadcastTransactionStream<S>
+{
+    fn drop(&mut self) {
+        let Ok(hub) = self.shared.hub.lock() else {
+            return;
+        };
+        // Stopped explicitly, or the broadcast already finished.
+        if matches!(hub.subscribers[self.id].state, SubscriberState::Stopped) {
+            return;
+        }
+        drop(hub);
+        let join_handle = self.shared.stop_subscriber(self.id);
+        match tokio::runtime::Handle::try_current() {
+            Ok(runtime) => {
+                runtime.spawn(self.shared.clone().release(join_handle));
+            }
+            // Without a runtime, the upstream stream can't be started,
+            // so the other subscribers are ended instead.
+            Err(_) => match join_handle {
+                Some(join_handle) => join_handle.abort(),
+                None if !self.shared.hub.lock().unwrap().started => {
+                    self.shared.finish(Some(anyhow::anyhow!(
+                        "A subscriber of the broadcast was dropped \
+                         outside of a Tokio runtime before it was started"
+                    )))
+                }
+                None => {}
+            },
+        }
+    }
+}
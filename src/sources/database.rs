@@ -1,16 +1,28 @@
 //! A transaction stream that fetches transactions from a Radix Gateway PostgreSQL database.
 
 use crate::{
-    models::{Event, EventEmitter, Transaction},
-    stream::TransactionStream,
+    event_handler::HandlerRegistry,
+    logger::Logger,
+    models::{
+        BlueprintId, Event, EventEmitter, Transaction, TransactionKind,
+        TransactionStatus,
+    },
+    sources::{
+        blueprint_cache::BlueprintCache, bounds::StreamBounds,
+        reporter::StreamReporter,
+    },
+    stream::{StateVersionSlot, TransactionStream},
 };
 use async_trait::async_trait;
-use chrono::Utc;
+use chrono::{DateTime, Utc};
 use radix_client::gateway::models::{EntityType, ModuleId};
 use serde::Deserialize;
 use sqlx::{postgres::PgConnectOptions, ConnectOptions};
-use std::{str::FromStr, time::Duration};
-use tokio::{sync::mpsc::Receiver, time::timeout};
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
+use tokio::{
+    sync::{mpsc::Receiver, RwLock},
+    time::timeout,
+};
 
 /// A transaction stream that fetches transactions directly from
 /// the PostgreSQL database associated with a Radix Gateway.
@@ -28,6 +40,33 @@ pub struct DatabaseTransactionStream {
     caught_up_timeout: Duration,
     query_timeout: Duration,
     database_url: String,
+    transaction_kinds: Vec<TransactionKind>,
+    include_failed_transactions: bool,
+    resolve_blueprints: bool,
+    stop_when_caught_up: bool,
+    bounds: StreamBounds,
+    from_timestamp: Option<DateTime<Utc>>,
+    resolved_from_state_version: StateVersionSlot,
+    filters: DatabaseStreamFilters,
+    reporter: StreamReporter,
+    reached_state_version: StateVersionSlot,
+}
+
+/// Filters that are applied in the database query, so that
+/// transactions which don't match are never fetched.
+/// A transaction is only fetched when it matches all filters that are set.
+#[derive(Debug, Clone, Default)]
+pub struct DatabaseStreamFilters {
+    /// Only transactions with events emitted by at least one of these
+    /// entities, or by an entity owned by one of them. This uses the
+    /// indexed transaction markers, so it is the most selective filter.
+    /// Addresses the database doesn't know yet are picked up once it does.
+    pub emitter_addresses: Vec<String>,
+    /// Only transactions with at least one event with one of these names.
+    pub event_names: Vec<String>,
+    /// Only transactions with at least one event emitted
+    /// by an entity of one of these types.
+    pub emitter_entity_types: Vec<EntityType>,
 }
 
 impl Default for DatabaseTransactionStream {
@@ -40,6 +79,16 @@ impl Default for DatabaseTransactionStream {
             caught_up_timeout: Duration::from_millis(500),
             query_timeout: Duration::from_secs(30),
             database_url: "".to_string(),
+            transaction_kinds: vec![TransactionKind::User],
+            include_failed_transactions: false,
+            resolve_blueprints: true,
+            stop_when_caught_up: false,
+            bounds: StreamBounds::default(),
+            from_timestamp: None,
+            resolved_from_state_version: StateVersionSlot::default(),
+            filters: DatabaseStreamFilters::default(),
+            reporter: StreamReporter::default(),
+            reached_state_version: StateVersionSlot::default(),
         }
     }
 }
@@ -59,6 +108,39 @@ impl DatabaseTransactionStream {
         self
     }
 
+    /// Starts the stream at the first transaction confirmed at or after
+    /// `timestamp`, instead of at a state version. The state version is
+    /// looked up once the stream is started, retrying like any other
+    /// query. It's reported to
+    /// [`Logger::stream_timestamp_resolved`] and available through
+    /// [`DatabaseTransactionStream::resolved_from_state_version`].
+    /// Resuming from a checkpoint takes precedence over this.
+    pub fn from_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.from_timestamp = Some(timestamp);
+        self
+    }
+
+    /// The state version that `from_timestamp` resolved to,
+    /// once it has been looked up after the stream was started.
+    pub fn resolved_from_state_version(&self) -> Option<u64> {
+        self.resolved_from_state_version.get()
+    }
+
+    /// Sets the last state version to fetch, inclusive. The stream
+    /// ends after this transaction, or when it has caught up with
+    /// a ledger that is past this state version.
+    pub fn to_state_version(mut self, state_version: u64) -> Self {
+        self.bounds.to_state_version = Some(state_version);
+        self
+    }
+
+    /// Ends the stream at the first transaction confirmed at or after
+    /// `timestamp`, which is not sent itself.
+    pub fn until_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.bounds.until_timestamp = Some(timestamp);
+        self
+    }
+
     /// Sets the max number of transactions to fetch per query.
     pub fn limit_per_page(mut self, limit_per_page: u32) -> Self {
         self.limit_per_page = limit_per_page;
@@ -86,8 +168,88 @@ impl DatabaseTransactionStream {
         self.query_timeout = timeout;
         self
     }
+
+    /// Sets the kinds of transactions to fetch. By default, only user
+    /// transactions are fetched. Include [`TransactionKind::RoundUpdate`]
+    /// to receive events like round and epoch changes.
+    pub fn transaction_kinds(
+        mut self,
+        transaction_kinds: impl IntoIterator<Item = TransactionKind>,
+    ) -> Self {
+        self.transaction_kinds = transaction_kinds.into_iter().collect();
+        self
+    }
+
+    /// Fetches transactions of all kinds.
+    pub fn all_transaction_kinds(self) -> Self {
+        self.transaction_kinds(TransactionKind::ALL)
+    }
+
+    /// Sets whether failed transactions are fetched too. They are skipped
+    /// by default. Event handlers have to opt in to receive their events,
+    /// see [`HandlerRegistry::set_handles_failed_transactions`][crate::event_handler::HandlerRegistry::set_handles_failed_transactions].
+    pub fn include_failed_transactions(
+        mut self,
+        include_failed_transactions: bool,
+    ) -> Self {
+        self.include_failed_transactions = include_failed_transactions;
+        self
+    }
+
+    /// Sets whether the blueprint of each method emitter is looked up in
+    /// the entities table, so that it can be used to match handlers.
+    /// This is enabled by default. Blueprints are cached, so each
+    /// entity is only looked up once.
+    pub fn resolve_blueprints(mut self, resolve_blueprints: bool) -> Self {
+        self.resolve_blueprints = resolve_blueprints;
+        self
+    }
+
+    /// Sets filters that are applied in the database query, so that
+    /// transactions which don't match are never fetched.
+    pub fn filters(mut self, filters: DatabaseStreamFilters) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Only fetches transactions with events from emitters that handlers are
+    /// registered for. If a handler can match events from any emitter,
+    /// all transactions are fetched.
+    ///
+    /// Use this only when the registry holds all handlers up front,
+    /// and the transaction handler doesn't need other transactions.
+    pub fn filter_by_handler_registry(
+        mut self,
+        handler_registry: &HandlerRegistry,
+    ) -> Self {
+        match handler_registry.emitter_addresses() {
+            Some(emitter_addresses) => {
+                log::info!(
+                    "Only fetching transactions with events from {} emitters",
+                    emitter_addresses.len()
+                );
+                self.filters.emitter_addresses = emitter_addresses;
+            }
+            None => log::warn!(
+                "Can't filter transactions by the handler registry, \
+                 because some handlers match events from any emitter"
+            ),
+        }
+        self
+    }
+
+    /// Sets whether the stream ends once it has caught up with the database,
+    /// instead of polling for new transactions. This is useful for backfills,
+    /// see [`HybridTransactionStream`][crate::sources::hybrid::HybridTransactionStream].
+    pub fn stop_when_caught_up(mut self, stop_when_caught_up: bool) -> Self {
+        self.stop_when_caught_up = stop_when_caught_up;
+        self
+    }
 }
 
+/// The max number of entities of which the blueprint is cached.
+const BLUEPRINT_CACHE_CAPACITY: usize = 100_000;
+
 /// A helper which is passed to the new task created by the stream.
 /// It keeps track of the current state version and fetches transactions
 /// from the database in batches. It sends the transactions to the
@@ -98,32 +260,119 @@ struct DatabaseFetcher {
     state_version: u64,
     caught_up_timeout: Duration,
     query_timeout: Duration,
+    transaction_kinds: Vec<String>,
+    include_failed_transactions: bool,
+    blueprint_cache: Option<BlueprintCache>,
+    stop_when_caught_up: bool,
+    bounds: StreamBounds,
+    /// The addresses of the emitters to filter on, if that filter is set.
+    /// They are resolved to the global entities whose transaction markers
+    /// are used in the same query as the transactions, so emitters which
+    /// the database doesn't know yet are picked up once it does.
+    emitter_addresses: Option<Vec<String>>,
+    event_names: Option<Vec<String>>,
+    emitter_entity_types: Option<Vec<String>>,
+    /// The timestamp to look up the state version to start at for.
+    from_timestamp: Option<DateTime<Utc>>,
+    reporter: StreamReporter,
+    resolved_from_state_version: StateVersionSlot,
+    reached_state_version: StateVersionSlot,
     tx: tokio::sync::mpsc::Sender<Transaction>,
 }
 
 impl DatabaseFetcher {
+    /// Connects to the database using the settings of the stream.
     async fn new(
-        database_url: String,
-        limit_per_page: u32,
-        state_version: u64,
-        caught_up_timeout: Duration,
-        query_timeout: Duration,
+        stream: &DatabaseTransactionStream,
         tx: tokio::sync::mpsc::Sender<Transaction>,
     ) -> Result<Self, anyhow::Error> {
-        let options = PgConnectOptions::from_str(&database_url)
+        let options = PgConnectOptions::from_str(&stream.database_url)
             .map_err(|err| anyhow::anyhow!("Invalid database URL: {}", err))?
             .disable_statement_logging();
         let connection = sqlx::postgres::PgPool::connect_with(options).await?;
+        let filters = &stream.filters;
+        if !filters.emitter_addresses.is_empty() {
+            Self::check_emitter_addresses(
+                &connection,
+                &filters.emitter_addresses,
+            )
+            .await?;
+        }
+        let emitter_entity_types = filters
+            .emitter_entity_types
+            .iter()
+            .map(|entity_type| {
+                serde_json::to_value(entity_type)?
+                    .as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| anyhow::anyhow!("Invalid entity type"))
+            })
+            .collect::<Result<Vec<_>, anyhow::Error>>()?;
         Ok(Self {
             connection,
-            limit_per_page,
-            state_version,
-            caught_up_timeout,
-            query_timeout,
+            limit_per_page: stream.limit_per_page,
+            state_version: stream.state_version,
+            caught_up_timeout: stream.caught_up_timeout,
+            query_timeout: stream.query_timeout,
+            transaction_kinds: stream
+                .transaction_kinds
+                .iter()
+                .map(|kind| kind.as_str().to_string())
+                .collect(),
+            include_failed_transactions: stream.include_failed_transactions,
+            blueprint_cache: stream
+                .resolve_blueprints
+                .then(|| BlueprintCache::new(BLUEPRINT_CACHE_CAPACITY)),
+            stop_when_caught_up: stream.stop_when_caught_up,
+            bounds: stream.bounds,
+            emitter_addresses: (!filters.emitter_addresses.is_empty())
+                .then(|| filters.emitter_addresses.clone()),
+            event_names: (!filters.event_names.is_empty())
+                .then(|| filters.event_names.clone()),
+            emitter_entity_types: (!emitter_entity_types.is_empty())
+                .then_some(emitter_entity_types),
+            from_timestamp: stream.from_timestamp,
+            reporter: stream.reporter.clone(),
+            resolved_from_state_version: stream
+                .resolved_from_state_version
+                .clone(),
+            reached_state_version: stream.reached_state_version.clone(),
             tx,
         })
     }
 
+    /// Warns about the emitter addresses to filter on that the database
+    /// doesn't know yet. Transactions from them are only fetched
+    /// once the database knows them.
+    async fn check_emitter_addresses(
+        connection: &sqlx::Pool<sqlx::Postgres>,
+        addresses: &[String],
+    ) -> Result<(), anyhow::Error> {
+        let (known,) = sqlx::query_as::<_, (i64,)>(
+            r#"
+                SELECT
+                    COUNT(*)
+                FROM
+                    entities
+                WHERE
+                    address = ANY($1)
+            "#,
+        )
+        .bind(addresses)
+        .fetch_one(connection)
+        .await?;
+        if (known as usize) < addresses.len() {
+            log::warn!(
+                "Only {} of the {} emitter addresses to filter on are known \
+                 in the database, transactions from the others are fetched \
+                 once they are",
+                known,
+                addresses.len()
+            );
+        }
+        Ok(())
+    }
+
     /// Fetches the next batch of transactions from the database.
     async fn next_batch(&mut self) -> Result<Vec<Transaction>, anyhow::Error> {
         let query = sqlx::query_as::<_, TransactionRecord>(
@@ -134,11 +383,45 @@ impl DatabaseFetcher {
                     receipt_event_emitters,
                     receipt_event_sbors,
                     receipt_event_names,
-                    intent_hash
+                    intent_hash,
+                    discriminator::text AS discriminator,
+                    receipt_status::text AS receipt_status,
+                    receipt_error_message
                 FROM
                     ledger_transactions
                 WHERE
-                    discriminator = 'user' AND receipt_status != 'failed' AND state_version >= $2
+                    discriminator::text = ANY($3)
+                    AND ($4 OR receipt_status != 'failed')
+                    AND state_version >= $2
+                    AND state_version <= $5
+                    AND (
+                        $6::text[] IS NULL
+                        OR state_version IN (
+                            SELECT marker.state_version
+                            FROM ledger_transaction_markers marker
+                            WHERE
+                                marker.discriminator = 'event_global_emitter'
+                                AND marker.entity_id IN (
+                                    SELECT COALESCE(global_ancestor_id, id)
+                                    FROM entities
+                                    WHERE address = ANY($6)
+                                )
+                                AND marker.state_version >= $2
+                                AND marker.state_version <= $5
+                        )
+                    )
+                    AND (
+                        $7::text[] IS NULL
+                        OR receipt_event_names && $7
+                    )
+                    AND (
+                        $8::text[] IS NULL
+                        OR EXISTS (
+                            SELECT 1
+                            FROM unnest(receipt_event_emitters) emitter
+                            WHERE emitter->'entity'->>'entity_type' = ANY($8)
+                        )
+                    )
                 ORDER BY
                     state_version ASC
                 LIMIT
@@ -146,16 +429,26 @@ impl DatabaseFetcher {
             "#
         )
         .bind(self.limit_per_page as i32)
-        .bind(self.state_version as i64);
+        .bind(self.state_version as i64)
+        .bind(&self.transaction_kinds)
+        .bind(self.include_failed_transactions)
+        .bind(
+            self.bounds
+                .to_state_version
+                .map_or(i64::MAX, |to| to as i64),
+        )
+        .bind(&self.emitter_addresses)
+        .bind(&self.event_names)
+        .bind(&self.emitter_entity_types);
 
         let transactions: Vec<TransactionRecord> =
             timeout(self.query_timeout, query.fetch_all(&self.connection))
                 .await??;
 
         // Convert the database records to the Transaction model
-        let transactions: Vec<_> = transactions
+        let mut transactions: Vec<_> = transactions
             .into_iter()
-            .map(|db_transaction| {
+            .map(|db_transaction| -> Result<_, anyhow::Error> {
                 let events = db_transaction
                     .receipt_event_emitters
                     .into_iter()
@@ -173,14 +466,23 @@ impl DatabaseFetcher {
                             .into(),
                     })
                     .collect();
-                Transaction {
+                Ok(Transaction {
                     state_version: db_transaction.state_version as u64,
-                    intent_hash: db_transaction.intent_hash.unwrap(),
+                    // Only user transactions have an intent hash
+                    intent_hash: db_transaction.intent_hash.unwrap_or_default(),
+                    kind: db_transaction.discriminator.parse()?,
+                    status: match db_transaction.receipt_status.as_str() {
+                        "failed" => TransactionStatus::CommittedFailure,
+                        _ => TransactionStatus::CommittedSuccess,
+                    },
+                    error_message: db_transaction.receipt_error_message,
                     confirmed_at: Some(db_transaction.round_timestamp),
                     events,
-                }
+                })
             })
-            .collect();
+            .collect::<Result<_, _>>()?;
+
+        self.resolve_blueprints(&mut transactions).await?;
 
         // Update the state version
         self.state_version = transactions
@@ -191,7 +493,141 @@ impl DatabaseFetcher {
         Ok(transactions)
     }
 
+    /// Returns the state version of the first transaction confirmed at or
+    /// after `timestamp`. If there is none yet, this is the state
+    /// version of the next transaction.
+    async fn state_version_at(
+        &self,
+        timestamp: DateTime<Utc>,
+    ) -> Result<u64, anyhow::Error> {
+        // Round timestamps only move forward, so sorting by them
+        // lets the query use the index on round_timestamp.
+        let query = sqlx::query_as::<_, (i64,)>(
+            r#"
+                SELECT
+                    COALESCE(
+                        (
+                            SELECT state_version
+                            FROM ledger_transactions
+                            WHERE round_timestamp >= $1
+                            ORDER BY round_timestamp ASC, state_version ASC
+                            LIMIT 1
+                        ),
+                        (
+                            SELECT MAX(state_version) + 1
+                            FROM ledger_transactions
+                        ),
+                        1
+                    )
+            "#,
+        )
+        .bind(timestamp);
+        let (state_version,) =
+            timeout(self.query_timeout, query.fetch_one(&self.connection))
+                .await??;
+        Ok(state_version as u64)
+    }
+
+    /// Returns the state version and timestamp of the last transaction
+    /// in the database, or `None` if there are no transactions yet.
+    async fn ledger_tip(
+        &self,
+    ) -> Result<Option<(u64, DateTime<Utc>)>, anyhow::Error> {
+        let query = sqlx::query_as::<_, (i64, DateTime<Utc>)>(
+            r#"
+                SELECT
+                    state_version,
+                    round_timestamp
+                FROM
+                    ledger_transactions
+                ORDER BY
+                    state_version DESC
+                LIMIT
+                    1
+            "#,
+        );
+        let tip =
+            timeout(self.query_timeout, query.fetch_optional(&self.connection))
+                .await??;
+        Ok(tip.map(|(state_version, timestamp)| {
+            (state_version as u64, timestamp)
+        }))
+    }
+
+    /// Attaches the blueprint to each method emitter in the transactions,
+    /// looking up the entities that aren't cached yet.
+    async fn resolve_blueprints(
+        &mut self,
+        transactions: &mut [Transaction],
+    ) -> Result<(), anyhow::Error> {
+        let Some(blueprint_cache) = &mut self.blueprint_cache else {
+            return Ok(());
+        };
+        let addresses = blueprint_cache.unknown_addresses(transactions, false);
+        if !addresses.is_empty() {
+            let query = sqlx::query_as::<_, (String, String, String)>(
+                r#"
+                    SELECT
+                        entity.address,
+                        package.address,
+                        entity.blueprint_name
+                    FROM
+                        entities entity
+                    INNER JOIN
+                        entities package ON package.id = entity.package_id
+                    WHERE
+                        entity.address = ANY($1)
+                "#,
+            )
+            .bind(&addresses);
+            let blueprints =
+                timeout(self.query_timeout, query.fetch_all(&self.connection))
+                    .await??
+                    .into_iter()
+                    .map(|(address, package_address, blueprint_name)| {
+                        (
+                            address,
+                            BlueprintId {
+                                package_address,
+                                blueprint_name,
+                            },
+                        )
+                    })
+                    .collect::<HashMap<_, _>>();
+            blueprint_cache.extend(addresses, blueprints);
+        }
+        blueprint_cache.attach(transactions);
+        Ok(())
+    }
+
+    /// Looks up the state version to start at, if the stream starts at a
+    /// timestamp. Like fetching a batch, this is retried until it succeeds.
+    async fn resolve_from_timestamp(&mut self) {
+        let Some(timestamp) = self.from_timestamp else {
+            return;
+        };
+        let mut response = self.state_version_at(timestamp).await;
+        while let Err(err) = response {
+            log::warn!(
+                "Error looking up the state version at {}: {:?}\n Trying again...",
+                timestamp,
+                err
+            );
+            response = self.state_version_at(timestamp).await;
+        }
+        let state_version = response.unwrap();
+        self.reporter
+            .timestamp_resolved(timestamp, state_version)
+            .await;
+        self.state_version = state_version;
+        self.resolved_from_state_version.set(state_version);
+    }
+
     async fn run(&mut self) {
+        self.resolve_from_timestamp().await;
+        // The ledger tip that was looked up after an empty batch,
+        // once the stream should end when it is caught up with it.
+        let mut caught_up_tip = None;
         loop {
             let mut response = self.next_batch().await;
             while let Err(err) = response {
@@ -203,13 +639,51 @@ impl DatabaseFetcher {
             }
             let transactions = response.unwrap();
             if transactions.is_empty() {
+                // This batch was fetched after the tip was looked up, so
+                // no transactions up to the tip were missed. Dropping
+                // the sender ends the stream.
+                if let Some(tip) = caught_up_tip {
+                    self.reached_state_version.set(self.bounds.reached_at(tip));
+                    return;
+                }
+                if self.stop_when_caught_up || self.bounds.is_bounded() {
+                    match self.ledger_tip().await {
+                        Ok(Some((state_version, timestamp)))
+                            if self.stop_when_caught_up
+                                || self
+                                    .bounds
+                                    .is_passed_by(state_version, timestamp) =>
+                        {
+                            caught_up_tip = Some(state_version);
+                            continue;
+                        }
+                        Ok(None) if self.stop_when_caught_up => return,
+                        Ok(_) => {}
+                        Err(err) => log::warn!(
+                            "Error fetching the ledger tip: {:?}",
+                            err
+                        ),
+                    }
+                }
                 tokio::time::sleep(self.caught_up_timeout).await;
             }
+            caught_up_tip = None;
 
             for transaction in transactions {
+                if !self.bounds.contains(&transaction) {
+                    self.reached_state_version
+                        .set(transaction.state_version.saturating_sub(1));
+                    return;
+                }
+                let state_version = transaction.state_version;
+                let is_last = self.bounds.is_last(&transaction);
                 if self.tx.send(transaction).await.is_err() {
                     return;
                 }
+                if is_last {
+                    self.reached_state_version.set(state_version);
+                    return;
+                }
             }
         }
     }
@@ -220,15 +694,11 @@ impl TransactionStream for DatabaseTransactionStream {
     async fn start(&mut self) -> Result<Receiver<Transaction>, anyhow::Error> {
         let (tx, rx) =
             tokio::sync::mpsc::channel(self.buffer_capacity as usize);
-        let mut fetcher = DatabaseFetcher::new(
-            self.database_url.clone(),
-            self.limit_per_page,
-            self.state_version,
-            self.caught_up_timeout,
-            self.query_timeout,
-            tx,
-        )
-        .await?;
+        self.reached_state_version = StateVersionSlot::default();
+        self.resolved_from_state_version = StateVersionSlot::default();
+        // The timestamp is looked up by the fetcher, so stopping
+        // the stream also stops retrying the lookup.
+        let mut fetcher = DatabaseFetcher::new(self, tx).await?;
         let handle = tokio::spawn(async move { fetcher.run().await });
         self.join_handle = Some(handle);
         Ok(rx)
@@ -239,6 +709,19 @@ impl TransactionStream for DatabaseTransactionStream {
             handle.abort();
         }
     }
+
+    fn resume_from_state_version(&mut self, state_version: u64) {
+        self.state_version = state_version;
+        self.from_timestamp = None;
+    }
+
+    fn set_logger(&mut self, logger: Arc<RwLock<Box<dyn Logger>>>) {
+        self.reporter.logger = Some(logger);
+    }
+
+    fn reached_state_version(&self) -> Option<u64> {
+        self.reached_state_version.get()
+    }
 }
 
 #[derive(sqlx::FromRow, Debug)] // Ensure this derive to work with sqlx queries
@@ -249,6 +732,9 @@ struct TransactionRecord {
     receipt_event_sbors: Vec<Vec<u8>>,
     receipt_event_names: Vec<String>,
     intent_hash: Option<String>,
+    discriminator: String,
+    receipt_status: String,
+    receipt_error_message: Option<String>,
 }
 
 #[derive(Deserialize, Debug, Clone)]
@@ -282,6 +768,8 @@ impl From<EventEmitterIdentifier> for EventEmitter {
                 entity_type: entity.entity_type,
                 is_global: entity.is_global,
                 object_module_id,
+                // Resolved by the fetcher, as it's not part of the event
+                blueprint: None,
             },
             EventEmitterIdentifier::Function {
                 package_address,
@@ -1,17 +1,53 @@
-//! A transaction stream that fetches transactions from a YAML or JSON file.
+//! A transaction stream that reads transactions from files.
+//!
+//! Files are decoded incrementally while the stream is running, so archives
+//! don't have to fit in memory. The following formats are supported, based
+//! on the file extension:
+//! - `.json`: a JSON array of transactions.
+//! - `.ndjson` or `.jsonl`: one JSON transaction per line.
+//! - `.yaml` or `.yml`: a YAML list of transactions, which is loaded as a whole.
+//! - `.bin`: a binary archive, see [`archive`][crate::sources::archive].
+//!   With an index, the stream seeks straight to its `from_state_version`
+//!   and skips earlier segments in a directory without reading them.
+//!
+//! Each of these can be compressed with gzip (`.gz`) or zstd (`.zst`),
+//! like `transactions.ndjson.zst`.
+//!
+//! Transactions are either in the format of the Gateway API, with a
+//! `unix_timestamp_nanos` field and Gateway events (see [`FileTransaction`]),
+//! or in the format of [`Transaction`] itself, as written by a
+//! [`RecordingStream`][crate::sources::recording::RecordingStream].
 
-use std::{fs::File, path::Path};
+use std::{
+    fmt,
+    fs::File,
+    io::{BufRead, BufReader, Seek, SeekFrom},
+    path::{Path, PathBuf},
+};
 
 use async_trait::async_trait;
-use serde::Deserialize;
+use serde::{
+    de::{self, SeqAccess, Visitor},
+    Deserialize, Deserializer,
+};
 use tokio::sync::mpsc::Receiver;
 
-use crate::{models::Transaction, stream::TransactionStream};
+use crate::{
+    models::{Transaction, TransactionKind, TransactionStatus},
+    sources::archive,
+    stream::{StreamErrorSlot, TransactionStream},
+};
 
 #[derive(Debug, Deserialize, Clone)]
 pub struct FileTransaction {
     pub intent_hash: String,
     pub state_version: u64,
+    #[serde(default)]
+    pub kind: TransactionKind,
+    #[serde(default)]
+    pub status: TransactionStatus,
+    #[serde(default)]
+    pub error_message: Option<String>,
     pub unix_timestamp_nanos: i64,
     pub events: Vec<radix_client::gateway::models::Event>,
 }
@@ -21,6 +57,9 @@ impl From<FileTransaction> for Transaction {
         Self {
             intent_hash: transaction.intent_hash,
             state_version: transaction.state_version,
+            kind: transaction.kind,
+            status: transaction.status,
+            error_message: transaction.error_message,
             confirmed_at: Some(chrono::DateTime::from_timestamp_nanos(
                 transaction.unix_timestamp_nanos,
             )),
@@ -33,47 +72,490 @@ impl From<FileTransaction> for Transaction {
     }
 }
 
+/// A stream that reads transactions from a file, or from a directory
+/// of numbered segment files which are read in order.
+/// See the [module documentation][crate::sources::file] for the supported formats.
 #[derive(Debug)]
 pub struct FileTransactionStream {
-    transactions: Vec<FileTransaction>,
+    path: PathBuf,
+    from_state_version: u64,
+    include_failed_transactions: bool,
+    error: StreamErrorSlot,
+    join_handle: Option<tokio::task::JoinHandle<()>>,
 }
 
 impl FileTransactionStream {
+    /// Creates a stream that reads the file or directory at `file_path`.
+    /// Nothing is read until the stream is started.
     pub fn new(file_path: String) -> Self {
-        let file = File::open(&file_path).expect("Unable to open file");
-
-        // Determine file extension
-        let extension = Path::new(&file_path)
-            .extension()
-            .and_then(std::ffi::OsStr::to_str)
-            .unwrap_or("");
-
-        let transactions: Vec<FileTransaction> = match extension {
-            "json" => serde_json::from_reader(&file)
-                .expect("Error deserializing JSON"),
-            "yaml" | "yml" => serde_yaml::from_reader(&file)
-                .expect("Error deserializing YAML"),
-            _ => panic!("Unsupported file type"),
-        };
+        Self {
+            path: PathBuf::from(file_path),
+            from_state_version: 0,
+            include_failed_transactions: false,
+            error: StreamErrorSlot::default(),
+            join_handle: None,
+        }
+    }
+
+    /// Skips all transactions in the file before this state version.
+    pub fn from_state_version(mut self, state_version: u64) -> Self {
+        self.from_state_version = state_version;
+        self
+    }
 
-        Self { transactions }
+    /// Sets whether failed transactions in the file are streamed too.
+    /// They are skipped by default.
+    pub fn include_failed_transactions(
+        mut self,
+        include_failed_transactions: bool,
+    ) -> Self {
+        self.include_failed_transactions = include_failed_transactions;
+        self
     }
 }
 
 #[async_trait]
 impl TransactionStream for FileTransactionStream {
     async fn start(&mut self) -> Result<Receiver<Transaction>, anyhow::Error> {
+        let mut files = source_files(&self.path)?;
+        let from_state_version = self.from_state_version;
+        // Skip segments that end before the state version to start at,
+        // as far as the indexes of the segments after them tell.
+        while files.len() > 1
+            && files[1].first_indexed_state_version()?.is_some_and(
+                |state_version| state_version <= from_state_version,
+            )
+        {
+            files.remove(0);
+        }
         let (tx, rx) = tokio::sync::mpsc::channel(32);
-        let transactions = self.transactions.clone();
-        tokio::spawn(async move {
-            for transaction in transactions.into_iter() {
-                if tx.send(transaction.into()).await.is_err() {
-                    break;
+        let include_failed_transactions = self.include_failed_transactions;
+        let error = self.error.clone();
+        // Decoding is blocking, so it's done on a thread where that's allowed.
+        let handle = tokio::task::spawn_blocking(move || {
+            let mut send = |transaction: Transaction| {
+                if transaction.state_version < from_state_version
+                    || !(include_failed_transactions
+                        || transaction.status.is_success())
+                {
+                    return true;
+                }
+                tx.blocking_send(transaction).is_ok()
+            };
+            for file in files {
+                match file.read(from_state_version, &mut send) {
+                    Ok(true) => {}
+                    // The receiver was dropped.
+                    Ok(false) => return,
+                    Err(err) => {
+                        error.set(err.context(format!(
+                            "Error reading transactions from {}",
+                            file.path.display()
+                        )));
+                        return;
+                    }
                 }
             }
         });
+        self.join_handle = Some(handle);
         Ok(rx)
     }
-    // no task is spawned, so no need to do anything on stop
-    async fn stop(&mut self) {}
+
+    // A blocking task can't be aborted, but it stops
+    // on its own once the receiver is dropped.
+    async fn stop(&mut self) {
+        self.join_handle.take();
+    }
+
+    fn resume_from_state_version(&mut self, state_version: u64) {
+        self.from_state_version = state_version;
+    }
+
+    fn take_error(&mut self) -> Option<anyhow::Error> {
+        self.error.take()
+    }
+}
+
+/// How the transactions in a file are encoded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum FileFormat {
+    /// A JSON array of transactions.
+    Json,
+    /// One JSON transaction per line.
+    JsonLines,
+    /// A YAML list of transactions. YAML can't be decoded
+    /// incrementally, so these files are loaded as a whole.
+    Yaml,
+    /// A binary archive, see [`archive`].
+    Binary,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Compression {
+    None,
+    Gzip,
+    Zstd,
+}
+
+/// The end of the transactions in a file, see [`SourceFile::tail`].
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Tail {
+    /// The state version of the last complete transaction,
+    /// or `None` if the file has none.
+    pub(super) last_state_version: Option<u64>,
+    /// The offset right after the last complete transaction. For a
+    /// compressed file, this is an offset in the decompressed data.
+    pub(super) end: u64,
+    /// Whether the file ends in a transaction that was only partly
+    /// written, after `end`.
+    pub(super) partial: bool,
+}
+
+/// A file to read transactions from.
+#[derive(Debug, Clone)]
+pub(super) struct SourceFile {
+    pub(super) path: PathBuf,
+    pub(super) format: FileFormat,
+    pub(super) compression: Compression,
+}
+
+impl SourceFile {
+    /// Determines the format and compression from the file extensions,
+    /// like `transactions.ndjson.zst`. Returns `None` for other files.
+    pub(super) fn new(path: PathBuf) -> Option<Self> {
+        let file_name = path.file_name()?.to_str()?;
+        let (file_name, compression) =
+            if let Some(file_name) = file_name.strip_suffix(".gz") {
+                (file_name, Compression::Gzip)
+            } else if let Some(file_name) = file_name
+                .strip_suffix(".zst")
+                .or_else(|| file_name.strip_suffix(".zstd"))
+            {
+                (file_name, Compression::Zstd)
+            } else {
+                (file_name, Compression::None)
+            };
+        let format = match Path::new(file_name).extension()?.to_str()? {
+            "json" => FileFormat::Json,
+            "ndjson" | "jsonl" => FileFormat::JsonLines,
+            "yaml" | "yml" => FileFormat::Yaml,
+            "bin" => FileFormat::Binary,
+            _ => return None,
+        };
+        Some(Self {
+            path,
+            format,
+            compression,
+        })
+    }
+
+    /// Whether the file can have an index. Compressed files can't
+    /// be indexed, because they can't be seeked.
+    pub(super) fn is_indexable(&self) -> bool {
+        self.format == FileFormat::Binary
+            && self.compression == Compression::None
+    }
+
+    fn index(&self) -> Result<Option<Vec<archive::IndexEntry>>, anyhow::Error> {
+        if !self.is_indexable() {
+            return Ok(None);
+        }
+        archive::read_index(&self.path)
+    }
+
+    fn first_indexed_state_version(
+        &self,
+    ) -> Result<Option<u64>, anyhow::Error> {
+        Ok(self
+            .index()?
+            .and_then(|index| index.first().map(|entry| entry.state_version)))
+    }
+
+    /// Opens the file, positioned at the first transaction.
+    /// For an indexed archive, the first transaction to read
+    /// may be further on, closer to `from_state_version`.
+    fn open(
+        &self,
+        from_state_version: u64,
+    ) -> Result<Box<dyn BufRead>, anyhow::Error> {
+        let mut file = File::open(&self.path)?;
+        if self.is_indexable() {
+            archive::read_header(&mut file)?;
+            if let Some(offset) = self.index()?.and_then(|index| {
+                archive::seek_offset(&index, from_state_version)
+            }) {
+                file.seek(SeekFrom::Start(offset))?;
+            }
+            return Ok(Box::new(BufReader::new(file)));
+        }
+        let mut reader: Box<dyn BufRead> = match self.compression {
+            Compression::None => Box::new(BufReader::new(file)),
+            Compression::Gzip => Box::new(BufReader::new(
+                flate2::read::MultiGzDecoder::new(file),
+            )),
+            Compression::Zstd => Box::new(BufReader::new(
+                zstd::stream::read::Decoder::new(file)?,
+            )),
+        };
+        if self.format == FileFormat::Binary {
+            archive::read_header(&mut reader)?;
+        }
+        Ok(reader)
+    }
+
+    /// Finds the last complete transaction in the file, and where it ends.
+    /// Only an indexed archive can skip ahead to its last transactions,
+    /// other files are read as a whole. Only the formats that can be
+    /// recorded to are supported.
+    pub(super) fn tail(&self) -> Result<Tail, anyhow::Error> {
+        let length = std::fs::metadata(&self.path)?.len();
+        // The last entry of the index can point at a transaction that was
+        // only partly written, in which case the one before it is used.
+        let mut offsets: Vec<u64> = self
+            .index()?
+            .unwrap_or_default()
+            .into_iter()
+            .map(|entry| entry.offset)
+            .filter(|&offset| offset < length)
+            .collect();
+        loop {
+            let offset = offsets.pop();
+            let tail = self.tail_from(offset)?;
+            if tail.last_state_version.is_some() || offset.is_none() {
+                return Ok(tail);
+            }
+        }
+    }
+
+    /// Reads the transactions from `offset` in an indexed archive,
+    /// or from the start, up to the end of the file.
+    fn tail_from(&self, offset: Option<u64>) -> Result<Tail, anyhow::Error> {
+        let mut tail = Tail {
+            last_state_version: None,
+            end: match self.format {
+                FileFormat::Binary => archive::HEADER_LENGTH,
+                _ => 0,
+            },
+            partial: false,
+        };
+        let mut reader: Box<dyn BufRead> = match offset {
+            Some(offset) => {
+                let mut file = File::open(&self.path)?;
+                archive::read_header(&mut file)?;
+                file.seek(SeekFrom::Start(offset))?;
+                tail.end = offset;
+                Box::new(BufReader::new(file))
+            }
+            None => self.open(0)?,
+        };
+        match self.format {
+            FileFormat::JsonLines => {
+                let mut line = Vec::new();
+                let mut line_number = 0;
+                loop {
+                    line.clear();
+                    let read = reader.read_until(b'\n', &mut line)?;
+                    if read == 0 {
+                        break;
+                    }
+                    line_number += 1;
+                    // Every line is written with its newline.
+                    if !line.ends_with(b"\n") {
+                        tail.partial = true;
+                        break;
+                    }
+                    if !line.iter().all(u8::is_ascii_whitespace) {
+                        let transaction = serde_json::from_slice(&line)
+                            .and_then(decode_transaction)
+                            .map_err(|err| {
+                                anyhow::anyhow!(
+                                    "Error decoding line {}: {}",
+                                    line_number,
+                                    err
+                                )
+                            })?;
+                        tail.last_state_version =
+                            Some(transaction.state_version);
+                    }
+                    tail.end += read as u64;
+                }
+            }
+            FileFormat::Binary => loop {
+                match archive::read_record(&mut reader) {
+                    Ok(Some(encoded)) => {
+                        let transaction = archive::decode_record(&encoded)?;
+                        tail.last_state_version =
+                            Some(transaction.state_version);
+                        tail.end += archive::record_length(&encoded);
+                    }
+                    Ok(None) => break,
+                    Err(err)
+                        if err.kind() == std::io::ErrorKind::UnexpectedEof =>
+                    {
+                        tail.partial = true;
+                        break;
+                    }
+                    Err(err) => return Err(err.into()),
+                }
+            },
+            FileFormat::Json | FileFormat::Yaml => {
+                return Err(anyhow::anyhow!(
+                    "Can't find the end of {}, only newline-delimited JSON and binary archives are supported",
+                    self.path.display()
+                ))
+            }
+        }
+        Ok(tail)
+    }
+
+    /// Decodes the transactions in the file one by one and passes them
+    /// to `send`, until it returns false. Returns whether all
+    /// transactions were passed.
+    fn read(
+        &self,
+        from_state_version: u64,
+        send: &mut impl FnMut(Transaction) -> bool,
+    ) -> Result<bool, anyhow::Error> {
+        let mut reader = self.open(from_state_version)?;
+        match self.format {
+            FileFormat::Json => {
+                let mut completed = true;
+                let result = serde_json::Deserializer::from_reader(reader)
+                    .deserialize_seq(SequenceVisitor {
+                        send: &mut |transaction| {
+                            completed = send(transaction);
+                            completed
+                        },
+                    });
+                // Stopping halfway makes the array look unfinished.
+                if completed {
+                    result?;
+                }
+                Ok(completed)
+            }
+            FileFormat::JsonLines => {
+                let mut line = String::new();
+                let mut line_number = 0;
+                loop {
+                    line.clear();
+                    if reader.read_line(&mut line)? == 0 {
+                        return Ok(true);
+                    }
+                    line_number += 1;
+                    if line.trim().is_empty() {
+                        continue;
+                    }
+                    let transaction = serde_json::from_str(&line)
+                        .and_then(decode_transaction)
+                        .map_err(|err| {
+                            anyhow::anyhow!(
+                                "Error decoding line {}: {}",
+                                line_number,
+                                err
+                            )
+                        })?;
+                    if !send(transaction) {
+                        return Ok(false);
+                    }
+                }
+            }
+            FileFormat::Yaml => {
+                let transactions: Vec<serde_json::Value> =
+                    serde_yaml::from_reader(reader)?;
+                for transaction in transactions {
+                    if !send(decode_transaction(transaction)?) {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            FileFormat::Binary => {
+                while let Some(transaction) =
+                    archive::read_transaction(&mut reader)?
+                {
+                    if !send(transaction) {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+        }
+    }
+}
+
+/// Decodes the elements of a JSON array one at a time.
+struct SequenceVisitor<'a, F: FnMut(Transaction) -> bool> {
+    send: &'a mut F,
+}
+
+impl<'de, F: FnMut(Transaction) -> bool> Visitor<'de>
+    for SequenceVisitor<'_, F>
+{
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("an array of transactions")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(transaction) = seq.next_element()? {
+            let transaction =
+                decode_transaction(transaction).map_err(de::Error::custom)?;
+            if !(self.send)(transaction) {
+                break;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Decodes a transaction in either of the supported formats. Only the
+/// Gateway format has a `unix_timestamp_nanos` field.
+fn decode_transaction(
+    value: serde_json::Value,
+) -> Result<Transaction, serde_json::Error> {
+    if value.get("unix_timestamp_nanos").is_some() {
+        serde_json::from_value::<FileTransaction>(value).map(Into::into)
+    } else {
+        serde_json::from_value(value)
+    }
+}
+
+/// Lists the files at the path. For a directory, these are the
+/// supported files in it, in the order of the number in their name.
+fn source_files(path: &Path) -> Result<Vec<SourceFile>, anyhow::Error> {
+    if !path.is_dir() {
+        return SourceFile::new(path.to_path_buf())
+            .map(|file| vec![file])
+            .ok_or_else(|| {
+                anyhow::anyhow!("Unsupported file type: {}", path.display())
+            });
+    }
+    let mut files = std::fs::read_dir(path)?
+        .map(|entry| Ok(entry?.path()))
+        .collect::<Result<Vec<_>, std::io::Error>>()?
+        .into_iter()
+        .filter(|path| path.is_file())
+        .filter_map(SourceFile::new)
+        .collect::<Vec<_>>();
+    files.sort_by_cached_key(|file| {
+        let file_name = file
+            .path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_string();
+        (segment_number(&file_name), file_name)
+    });
+    Ok(files)
+}
+
+/// The first number in a file name, like 12 in `segment-0012.ndjson`.
+fn segment_number(file_name: &str) -> Option<u64> {
+    let digits: String = file_name
+        .chars()
+        .skip_while(|c| !c.is_ascii_digit())
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok()
 }
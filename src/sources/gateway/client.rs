@@ -0,0 +1,409 @@
+//! A minimal client for the parts of the Radix Gateway API
+//! that aren't covered by the radix-client crate.
+
+use super::{filters::GatewayStreamFilters, rate_limiter::RateLimiter};
+use crate::{models::BlueprintId, stream::StreamErrorKind};
+use chrono::{DateTime, Utc};
+use radix_client::gateway::models::CommittedTransactionInfo;
+use reqwest::{
+    header::{HeaderMap, HeaderName, HeaderValue},
+    StatusCode,
+};
+use serde::Deserialize;
+use serde_json::{json, Map, Value};
+use std::{collections::HashMap, fmt, sync::Arc, time::Duration};
+
+/// The max number of addresses the Gateway accepts in a single
+/// request to the entity details endpoint.
+const MAX_ADDRESSES_PER_REQUEST: usize = 20;
+
+/// The number of transactions fetched to find the first
+/// transaction at or after a timestamp.
+const STATE_VERSION_LOOKUP_PAGE_SIZE: u32 = 10;
+
+/// The max number of characters of an error response
+/// that is included in a [`GatewayResponseError`].
+const MAX_ERROR_MESSAGE_LENGTH: usize = 1000;
+
+/// A client for the Radix Gateway API.
+#[derive(Debug, Clone)]
+pub struct GatewayApiClient {
+    client: reqwest::Client,
+    gateway_url: String,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+impl GatewayApiClient {
+    pub fn new(gateway_url: String) -> Self {
+        Self::with_client(reqwest::Client::new(), gateway_url)
+    }
+
+    /// Creates a client that sends its requests with a preconfigured
+    /// HTTP client, for example with authentication headers.
+    pub fn with_client(client: reqwest::Client, gateway_url: String) -> Self {
+        Self {
+            client,
+            gateway_url,
+            rate_limiter: Arc::new(RateLimiter::unlimited()),
+        }
+    }
+
+    /// Limits the rate of requests to `requests_per_second`, allowing
+    /// bursts of up to `burst` requests after a quiet period. The limit
+    /// is shared with clones of this client.
+    pub fn rate_limit(mut self, requests_per_second: f64, burst: u32) -> Self {
+        self.rate_limiter =
+            Arc::new(RateLimiter::new(requests_per_second, burst));
+        self
+    }
+
+    /// Holds back requests for `duration`, for example
+    /// when the Gateway asked to retry after a while.
+    pub async fn pause(&self, duration: Duration) {
+        self.rate_limiter.pause(duration).await;
+    }
+
+    /// Sends a request once the rate limiter allows it,
+    /// and turns an error response into a [`GatewayResponseError`].
+    async fn send(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> Result<reqwest::Response, anyhow::Error> {
+        self.rate_limiter.acquire().await;
+        let response = request.send().await?;
+        let status = response.status();
+        if !status.is_client_error() && !status.is_server_error() {
+            return Ok(response);
+        }
+        let url = response.url().to_string();
+        let retry_after = retry_after(response.headers());
+        let mut message = response.text().await.unwrap_or_default();
+        if let Some((index, _)) =
+            message.char_indices().nth(MAX_ERROR_MESSAGE_LENGTH)
+        {
+            message.truncate(index);
+        }
+        Err(GatewayResponseError {
+            status,
+            url,
+            retry_after,
+            message,
+        }
+        .into())
+    }
+
+    /// The URL of the Gateway this client sends requests to.
+    pub fn gateway_url(&self) -> &str {
+        &self.gateway_url
+    }
+
+    /// Fetches the current state of the ledger using the
+    /// `/status/gateway-status` endpoint.
+    pub async fn ledger_state(&self) -> Result<LedgerState, anyhow::Error> {
+        let response: GatewayStatusResponse = self
+            .send(
+                self.client.post(format!(
+                    "{}/status/gateway-status",
+                    self.gateway_url
+                )),
+            )
+            .await?
+            .json()
+            .await?;
+        Ok(response.ledger_state)
+    }
+
+    /// Fetches a page of committed transactions with their receipt events,
+    /// starting at `from_state_version`, using the `/stream/transactions`
+    /// endpoint. When `at_state_version` is set, the page only holds
+    /// transactions up to that state version.
+    pub async fn transactions(
+        &self,
+        from_state_version: u64,
+        at_state_version: Option<u64>,
+        limit_per_page: u32,
+        filters: &GatewayStreamFilters,
+        event_global_emitter: Option<&str>,
+    ) -> Result<Vec<CommittedTransactionInfo>, anyhow::Error> {
+        let mut request = Map::new();
+        request.insert(
+            "from_ledger_state".to_string(),
+            json!({ "state_version": from_state_version }),
+        );
+        if let Some(at_state_version) = at_state_version {
+            request.insert(
+                "at_ledger_state".to_string(),
+                json!({ "state_version": at_state_version }),
+            );
+        }
+        request.insert("order".to_string(), json!("Asc"));
+        request.insert("limit_per_page".to_string(), json!(limit_per_page));
+        request
+            .insert("opt_ins".to_string(), json!({ "receipt_events": true }));
+        filters.add_to_request(&mut request, event_global_emitter);
+        let response: TransactionStreamPage = self
+            .send(
+                self.client
+                    .post(format!("{}/stream/transactions", self.gateway_url))
+                    .json(&Value::Object(request)),
+            )
+            .await?
+            .json()
+            .await?;
+        Ok(response.items)
+    }
+
+    /// Returns the state version of the first transaction confirmed at or
+    /// after `timestamp`, using the `/stream/transactions` endpoint starting
+    /// from the ledger state at that time. If there is none yet, this is
+    /// the state version of the next transaction.
+    pub async fn state_version_at(
+        &self,
+        timestamp: DateTime<Utc>,
+    ) -> Result<u64, anyhow::Error> {
+        let response: TransactionStreamResponse = self
+            .send(
+                self.client
+                    .post(format!("{}/stream/transactions", self.gateway_url))
+                    .json(&json!({
+                        "from_ledger_state": { "timestamp": timestamp },
+                        "kind_filter": "All",
+                        "order": "Asc",
+                        "limit_per_page": STATE_VERSION_LOOKUP_PAGE_SIZE,
+                    })),
+            )
+            .await?
+            .json()
+            .await?;
+        // The ledger state at a timestamp is the last one before it, so
+        // the first transactions may have been confirmed a bit earlier.
+        let first = response
+            .items
+            .iter()
+            .find(|item| item.confirmed_at.map_or(true, |at| at >= timestamp));
+        Ok(match (first, response.items.last()) {
+            (Some(item), _) => item.state_version,
+            (None, Some(last)) => last.state_version + 1,
+            (None, None) => response.ledger_state.state_version + 1,
+        })
+    }
+
+    /// Looks up the blueprint of each of the given entities using the
+    /// `/state/entity/details` endpoint. Entities that weren't instantiated
+    /// from a blueprint, like vaults and resources, are left out.
+    pub async fn entity_blueprints(
+        &self,
+        addresses: &[String],
+    ) -> Result<HashMap<String, BlueprintId>, anyhow::Error> {
+        let mut blueprints = HashMap::new();
+        for addresses in addresses.chunks(MAX_ADDRESSES_PER_REQUEST) {
+            let response: EntityDetailsResponse = self
+                .send(
+                    self.client
+                        .post(format!(
+                            "{}/state/entity/details",
+                            self.gateway_url
+                        ))
+                        .json(&json!({ "addresses": addresses })),
+                )
+                .await?
+                .json()
+                .await?;
+            blueprints.extend(response.items.into_iter().filter_map(|item| {
+                let details = item.details?;
+                Some((
+                    item.address,
+                    BlueprintId {
+                        package_address: details.package_address?,
+                        blueprint_name: details.blueprint_name?,
+                    },
+                ))
+            }));
+        }
+        Ok(blueprints)
+    }
+}
+
+/// Settings for the HTTP client that sends requests to the Gateway.
+#[derive(Clone, Default)]
+pub(crate) struct HttpClientConfig {
+    pub(crate) client: Option<reqwest::Client>,
+    pub(crate) headers: Vec<(String, String)>,
+    pub(crate) request_timeout: Option<Duration>,
+    pub(crate) connect_timeout: Option<Duration>,
+    pub(crate) user_agent: Option<String>,
+    pub(crate) proxy: Option<String>,
+}
+
+// Header values often hold API keys, so they are left out.
+impl fmt::Debug for HttpClientConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let header_names: Vec<&str> =
+            self.headers.iter().map(|(name, _)| name.as_str()).collect();
+        f.debug_struct("HttpClientConfig")
+            .field("client", &self.client)
+            .field("headers", &header_names)
+            .field("request_timeout", &self.request_timeout)
+            .field("connect_timeout", &self.connect_timeout)
+            .field("user_agent", &self.user_agent)
+            .field("proxy", &self.proxy)
+            .finish()
+    }
+}
+
+impl HttpClientConfig {
+    fn is_customized(&self) -> bool {
+        !self.headers.is_empty()
+            || self.request_timeout.is_some()
+            || self.connect_timeout.is_some()
+            || self.user_agent.is_some()
+            || self.proxy.is_some()
+    }
+
+    /// Builds the HTTP client, or returns the one that was provided.
+    pub(crate) fn build(&self) -> Result<reqwest::Client, anyhow::Error> {
+        if let Some(client) = &self.client {
+            if self.is_customized() {
+                log::warn!(
+                    "An HTTP client was provided for the Gateway stream, \
+                     so its other HTTP settings are ignored"
+                );
+            }
+            return Ok(client.clone());
+        }
+        let mut headers = HeaderMap::new();
+        for (name, value) in &self.headers {
+            let mut value = HeaderValue::from_str(value).map_err(|err| {
+                anyhow::anyhow!("Invalid value for header {}: {}", name, err)
+            })?;
+            value.set_sensitive(true);
+            headers.append(HeaderName::from_bytes(name.as_bytes())?, value);
+        }
+        let mut builder = reqwest::Client::builder().default_headers(headers);
+        if let Some(request_timeout) = self.request_timeout {
+            builder = builder.timeout(request_timeout);
+        }
+        if let Some(connect_timeout) = self.connect_timeout {
+            builder = builder.connect_timeout(connect_timeout);
+        }
+        if let Some(user_agent) = &self.user_agent {
+            builder = builder.user_agent(user_agent);
+        }
+        if let Some(proxy) = &self.proxy {
+            builder = builder.proxy(reqwest::Proxy::all(proxy)?);
+        }
+        Ok(builder.build()?)
+    }
+}
+
+/// An error response from the Gateway.
+#[derive(Debug)]
+pub struct GatewayResponseError {
+    pub status: StatusCode,
+    pub url: String,
+    /// How long the Gateway asked to wait before trying again,
+    /// from the `Retry-After` header.
+    pub retry_after: Option<Duration>,
+    /// The start of the response body, which usually explains the error.
+    pub message: String,
+}
+
+impl fmt::Display for GatewayResponseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "HTTP status {} for url ({})", self.status, self.url)?;
+        if !self.message.is_empty() {
+            write!(f, ": {}", self.message)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for GatewayResponseError {}
+
+/// Parses the `Retry-After` header, which holds either
+/// a number of seconds or a date.
+fn retry_after(headers: &HeaderMap) -> Option<Duration> {
+    let value = headers.get(reqwest::header::RETRY_AFTER)?.to_str().ok()?;
+    if let Ok(seconds) = value.trim().parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = DateTime::parse_from_rfc2822(value.trim()).ok()?;
+    Some(
+        (date.with_timezone(&Utc) - Utc::now())
+            .to_std()
+            .unwrap_or_default(),
+    )
+}
+
+/// Returns how long the Gateway asked to wait before trying again,
+/// if the error is a response with a `Retry-After` header.
+pub fn requested_delay(error: &anyhow::Error) -> Option<Duration> {
+    error
+        .downcast_ref::<GatewayResponseError>()
+        .and_then(|error| error.retry_after)
+}
+
+/// Classifies an error returned by the client. Rate limiting, server
+/// errors and network errors are worth trying again, while other
+/// rejected requests will fail the same way every time.
+pub fn classify_error(error: &anyhow::Error) -> StreamErrorKind {
+    let status = match error.downcast_ref::<GatewayResponseError>() {
+        Some(error) => Some(error.status),
+        None => error
+            .downcast_ref::<reqwest::Error>()
+            .and_then(reqwest::Error::status),
+    };
+    match status {
+        Some(StatusCode::TOO_MANY_REQUESTS) => StreamErrorKind::RateLimited,
+        Some(StatusCode::REQUEST_TIMEOUT) => StreamErrorKind::Transient,
+        Some(status) if status.is_client_error() => StreamErrorKind::Permanent,
+        _ => StreamErrorKind::Transient,
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct TransactionStreamPage {
+    items: Vec<CommittedTransactionInfo>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TransactionStreamResponse {
+    ledger_state: LedgerState,
+    items: Vec<TransactionStreamItem>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TransactionStreamItem {
+    state_version: u64,
+    confirmed_at: Option<DateTime<Utc>>,
+}
+
+/// The state of the ledger that a Gateway has synced up to.
+#[derive(Deserialize, Debug, Clone)]
+pub struct LedgerState {
+    pub state_version: u64,
+    pub proposer_round_timestamp: DateTime<Utc>,
+}
+
+#[derive(Deserialize, Debug)]
+struct GatewayStatusResponse {
+    ledger_state: LedgerState,
+}
+
+#[derive(Deserialize, Debug)]
+struct EntityDetailsResponse {
+    items: Vec<EntityDetailsItem>,
+}
+
+#[derive(Deserialize, Debug)]
+struct EntityDetailsItem {
+    address: String,
+    details: Option<EntityDetails>,
+}
+
+/// Only component details hold a package address and blueprint name.
+#[derive(Deserialize, Debug)]
+struct EntityDetails {
+    package_address: Option<String>,
+    blueprint_name: Option<String>,
+}
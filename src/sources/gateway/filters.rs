@@ -0,0 +1,114 @@
+//! Filters that let the Gateway skip transactions before they are sent.
+
+use crate::event_handler::HandlerRegistry;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+/// Filters for the `/stream/transactions` endpoint of the Gateway.
+/// A transaction is only streamed when it matches all filters that are set.
+///
+/// Filtering on the Gateway saves fetching and decoding transactions that
+/// have no events to handle, which makes catching up a lot faster.
+#[derive(Debug, Clone, Default)]
+pub struct GatewayStreamFilters {
+    /// Only transactions with events emitted by at least one of these
+    /// global entities. Events from other emitters in those transactions
+    /// are still included.
+    pub event_global_emitters: Vec<String>,
+    /// Only transactions that affected all of these global entities.
+    pub affected_global_entities: Vec<String>,
+    /// Only transactions with a manifest of this class.
+    pub manifest_class: Option<ManifestClass>,
+    /// Only transactions with a manifest that withdraws
+    /// from all of these accounts.
+    pub manifest_accounts_withdrawn_from: Vec<String>,
+    /// Only transactions with a manifest that deposits
+    /// into all of these accounts.
+    pub manifest_accounts_deposited_into: Vec<String>,
+    /// Only transactions with a manifest that references
+    /// all of these resources.
+    pub manifest_resources: Vec<String>,
+}
+
+/// The classes the Gateway assigns to transaction manifests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum ManifestClass {
+    General,
+    Transfer,
+    PoolContribution,
+    PoolRedemption,
+    ValidatorStake,
+    ValidatorUnstake,
+    ValidatorClaim,
+    AccountDepositSettingsUpdate,
+}
+
+impl GatewayStreamFilters {
+    /// Creates filters which only let through transactions with events from
+    /// the emitters that handlers are registered for. Returns `None` when
+    /// that isn't possible, because a handler can match events from any
+    /// emitter, or is registered for an internal entity like a vault,
+    /// which the Gateway can't filter on.
+    ///
+    /// The filters are derived from the handlers that are registered
+    /// at this moment, so they don't include handlers that are added
+    /// later on, for example by other handlers.
+    pub fn from_handler_registry(
+        handler_registry: &HandlerRegistry,
+    ) -> Option<Self> {
+        let emitters = handler_registry.emitter_addresses()?;
+        if emitters.is_empty()
+            || emitters
+                .iter()
+                .any(|address| address.starts_with("internal_"))
+        {
+            return None;
+        }
+        Some(Self {
+            event_global_emitters: emitters,
+            ..Self::default()
+        })
+    }
+
+    /// Adds the filters to a request body. The Gateway only returns
+    /// transactions with events from all emitters in its filter,
+    /// so at most one emitter is passed at a time.
+    pub(crate) fn add_to_request(
+        &self,
+        request: &mut Map<String, Value>,
+        event_global_emitter: Option<&str>,
+    ) {
+        if let Some(emitter) = event_global_emitter {
+            request.insert(
+                "event_global_emitters_filter".to_string(),
+                json!([emitter]),
+            );
+        }
+        let address_filters = [
+            (
+                "affected_global_entities_filter",
+                &self.affected_global_entities,
+            ),
+            (
+                "manifest_accounts_withdrawn_from_filter",
+                &self.manifest_accounts_withdrawn_from,
+            ),
+            (
+                "manifest_accounts_deposited_into_filter",
+                &self.manifest_accounts_deposited_into,
+            ),
+            ("manifest_resources_filter", &self.manifest_resources),
+        ];
+        for (name, addresses) in address_filters {
+            if !addresses.is_empty() {
+                request.insert(name.to_string(), json!(addresses));
+            }
+        }
+        if let Some(manifest_class) = self.manifest_class {
+            request.insert(
+                "manifest_class_filter".to_string(),
+                json!({ "class": manifest_class }),
+            );
+        }
+    }
+}
@@ -0,0 +1,818 @@
+//! A transaction stream that fetches transactions from a Radix Gateway API.
+
+pub mod client;
+pub mod filters;
+mod rate_limiter;
+
+use crate::{
+    encodings::programmatic_json_to_bytes,
+    event_handler::HandlerRegistry,
+    logger::Logger,
+    models::{
+        Event, EventEmitter, Transaction, TransactionKind, TransactionStatus,
+    },
+    retry::RetryPolicy,
+    sources::{
+        blueprint_cache::BlueprintCache, bounds::StreamBounds,
+        reporter::StreamReporter,
+    },
+    stream::{
+        StateVersionSlot, StreamErrorKind, StreamErrorSlot, TransactionStream,
+    },
+};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use client::{GatewayApiClient, HttpClientConfig};
+use filters::GatewayStreamFilters;
+use radix_client::gateway::models::Event as GatewayEvent;
+use radix_client::gateway::models::{
+    CommittedTransactionInfo, EventEmitterIdentifier,
+    TransactionStatus as GatewayTransactionStatus,
+};
+use std::{
+    collections::{BTreeMap, HashSet},
+    sync::Arc,
+    time::Duration,
+};
+use tokio::{
+    sync::{
+        mpsc::{Receiver, Sender},
+        RwLock,
+    },
+    time::sleep,
+};
+
+impl From<GatewayEvent> for Event {
+    fn from(event: GatewayEvent) -> Self {
+        let emitter = match event.emitter {
+            EventEmitterIdentifier::Method {
+                entity,
+                object_module_id,
+            } => EventEmitter::Method {
+                entity_address: entity.entity_address,
+                entity_type: entity.entity_type,
+                is_global: entity.is_global,
+                object_module_id,
+                // Resolved by the fetcher, as it's not part of the event
+                blueprint: None,
+            },
+            EventEmitterIdentifier::Function {
+                package_address,
+                blueprint_name,
+            } => EventEmitter::Function {
+                package_address,
+                blueprint_name,
+            },
+        };
+        Self {
+            name: event.name,
+            emitter, 
+            json_sbor_data: Some(event.data.clone()), 
+            binary_sbor_data: programmatic_json_to_bytes(&event.data).expect(
+                "Should always able to convert Programmatic JSON to binary SBOR",
+            ), 
+        }
+    }
+}
+
+impl From<CommittedTransactionInfo> for Transaction {
+    fn from(transaction: CommittedTransactionInfo) -> Self {
+        let receipt = transaction
+            .receipt
+            .expect("Transaction should have receipt");
+        let status = match receipt.status {
+            Some(GatewayTransactionStatus::CommittedFailure) => {
+                TransactionStatus::CommittedFailure
+            }
+            Some(GatewayTransactionStatus::Rejected) => {
+                TransactionStatus::Rejected
+            }
+            _ => TransactionStatus::CommittedSuccess,
+        };
+        Self {
+            intent_hash: transaction
+                .intent_hash
+                .expect("Transaction should have tx id"),
+            state_version: transaction.state_version,
+            kind: TransactionKind::User,
+            status,
+            error_message: receipt.error_message,
+            confirmed_at: transaction.confirmed_at,
+            events: receipt
+                .events
+                .expect("Transaction receipt should have events")
+                .into_iter()
+                .map(|event| event.into())
+                .collect(),
+        }
+    }
+}
+
+/// A struct that fetches transactions from a Radix Gateway API.
+/// It uses a builder pattern for initialization, with some sensible defaults.
+#[derive(Debug)]
+pub struct GatewayTransactionStream {
+    gateway_url: String,
+    fallback_gateway_urls: Vec<String>,
+    http_client: HttpClientConfig,
+    rate_limit: Option<(f64, u32)>,
+    max_requested_delay: Duration,
+    retry_policy: RetryPolicy,
+    reporter: StreamReporter,
+    from_state_version: u64,
+    limit_per_page: u32,
+    buffer_capacity: u64,
+    caught_up_timeout: Duration,
+    include_failed_transactions: bool,
+    resolve_blueprints: bool,
+    bounds: StreamBounds,
+    from_timestamp: Option<DateTime<Utc>>,
+    resolved_from_state_version: StateVersionSlot,
+    filters: GatewayStreamFilters,
+    max_emitter_filters: usize,
+    error: StreamErrorSlot,
+    reached_state_version: StateVersionSlot,
+    handle: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl Default for GatewayTransactionStream {
+    fn default() -> Self {
+        Self {
+            gateway_url: "https://mainnet.radixdlt.com".to_string(),
+            fallback_gateway_urls: Vec::new(),
+            http_client: HttpClientConfig::default(),
+            rate_limit: None,
+            max_requested_delay: DEFAULT_MAX_REQUESTED_DELAY,
+            retry_policy: RetryPolicy::exponential(
+                Duration::from_millis(500),
+                Duration::from_secs(30),
+            )
+            .jitter(0.5),
+            reporter: StreamReporter::default(),
+            from_state_version: 1,
+            limit_per_page: 100,
+            buffer_capacity: 10_000,
+            caught_up_timeout: Duration::from_millis(500),
+            include_failed_transactions: false,
+            resolve_blueprints: true,
+            bounds: StreamBounds::default(),
+            from_timestamp: None,
+            resolved_from_state_version: StateVersionSlot::default(),
+            filters: GatewayStreamFilters::default(),
+            max_emitter_filters: DEFAULT_MAX_EMITTER_FILTERS,
+            error: StreamErrorSlot::default(),
+            reached_state_version: StateVersionSlot::default(),
+            handle: None,
+        }
+    }
+}
+
+impl GatewayTransactionStream {
+    /// Creates a new GatewayTransactionStream with default settings.
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// Sets the state version to start fetching transactions from.
+    /// This is inclusive, so the transaction with this state version will be included.
+    pub fn from_state_version(mut self, from_state_version: u64) -> Self {
+        self.from_state_version = from_state_version;
+        self
+    }
+
+    /// Starts the stream at the first transaction confirmed at or after
+    /// `timestamp`, instead of at a state version. The state version is
+    /// looked up once the stream is started, with the same retries and
+    /// fallback Gateways as other requests. It's reported to
+    /// [`Logger::stream_timestamp_resolved`] and available through
+    /// [`GatewayTransactionStream::resolved_from_state_version`].
+    /// Resuming from a checkpoint takes precedence over this.
+    pub fn from_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.from_timestamp = Some(timestamp);
+        self
+    }
+
+    /// The state version that `from_timestamp` resolved to,
+    /// once it has been looked up after the stream was started.
+    pub fn resolved_from_state_version(&self) -> Option<u64> {
+        self.resolved_from_state_version.get()
+    }
+
+    /// Sets the last state version to fetch, inclusive. The stream
+    /// ends after this transaction, or when it has caught up with
+    /// a ledger that is past this state version.
+    pub fn to_state_version(mut self, to_state_version: u64) -> Self {
+        self.bounds.to_state_version = Some(to_state_version);
+        self
+    }
+
+    /// Ends the stream at the first transaction confirmed at or after
+    /// `timestamp`, which is not sent itself.
+    pub fn until_timestamp(mut self, timestamp: DateTime<Utc>) -> Self {
+        self.bounds.until_timestamp = Some(timestamp);
+        self
+    }
+
+    /// Sets the URL of the Radix Gateway API to fetch transactions from.
+    pub fn gateway_url(mut self, gateway_url: String) -> Self {
+        self.gateway_url = gateway_url;
+        self
+    }
+
+    /// Sets Gateways to fall back to, in order, when requests to the
+    /// current one fail. After each failed request, the next request goes
+    /// to the next Gateway in the list, wrapping around to the one set with
+    /// [`GatewayTransactionStream::gateway_url`]. These should all be
+    /// Gateways for the same network.
+    pub fn fallback_gateway_urls(
+        mut self,
+        fallback_gateway_urls: Vec<String>,
+    ) -> Self {
+        self.fallback_gateway_urls = fallback_gateway_urls;
+        self
+    }
+
+    /// Adds a header to every request to the Gateway, like the API key
+    /// that a Gateway provider requires. Values are never logged.
+    ///
+    /// ```ignore
+    /// let stream = GatewayTransactionStream::new()
+    ///     .gateway_url("https://gateway.example.com".to_string())
+    ///     .header("x-api-key".to_string(), api_key);
+    /// ```
+    pub fn header(mut self, name: String, value: String) -> Self {
+        self.http_client.headers.push((name, value));
+        self
+    }
+
+    /// Sets the timeout for each request to the Gateway, from connecting
+    /// until the response has been read. There is no timeout by default.
+    /// A request that times out is retried like any other failed request.
+    pub fn request_timeout(mut self, request_timeout: Duration) -> Self {
+        self.http_client.request_timeout = Some(request_timeout);
+        self
+    }
+
+    /// Sets the timeout for connecting to the Gateway.
+    pub fn connect_timeout(mut self, connect_timeout: Duration) -> Self {
+        self.http_client.connect_timeout = Some(connect_timeout);
+        self
+    }
+
+    /// Sets the `User-Agent` header of requests to the Gateway.
+    pub fn user_agent(mut self, user_agent: String) -> Self {
+        self.http_client.user_agent = Some(user_agent);
+        self
+    }
+
+    /// Sends all requests to the Gateway through a proxy,
+    /// like `http://proxy.example.com:8080`.
+    pub fn proxy(mut self, proxy_url: String) -> Self {
+        self.http_client.proxy = Some(proxy_url);
+        self
+    }
+
+    /// Sends requests to the Gateway with a preconfigured HTTP client,
+    /// for settings that aren't covered by the other methods. When set,
+    /// the headers, timeouts, user agent and proxy set on the stream
+    /// are ignored.
+    pub fn http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client.client = Some(http_client);
+        self
+    }
+
+    /// Limits the rate of requests to each Gateway to `requests_per_second`,
+    /// allowing bursts of up to `burst` requests. There is no limit by
+    /// default, so the stream fetches as fast as it can while catching up.
+    ///
+    /// Independent of this, when a Gateway responds with a `Retry-After`
+    /// header, no requests are sent to it until that time has passed.
+    pub fn rate_limit(mut self, requests_per_second: f64, burst: u32) -> Self {
+        self.rate_limit = Some((requests_per_second, burst));
+        self
+    }
+
+    /// Sets the longest a Gateway can ask the stream to wait with a
+    /// `Retry-After` header, which is 5 minutes by default. Longer
+    /// delays are cut down to this.
+    pub fn max_requested_delay(
+        mut self,
+        max_requested_delay: Duration,
+    ) -> Self {
+        self.max_requested_delay = max_requested_delay;
+        self
+    }
+
+    /// Creates a client for the Gateway at `gateway_url`
+    /// with the settings of the stream.
+    fn api_client(
+        &self,
+        http_client: &reqwest::Client,
+        gateway_url: &str,
+    ) -> GatewayApiClient {
+        let api_client = GatewayApiClient::with_client(
+            http_client.clone(),
+            gateway_url.to_string(),
+        );
+        match self.rate_limit {
+            Some((requests_per_second, burst)) => {
+                api_client.rate_limit(requests_per_second, burst)
+            }
+            None => api_client,
+        }
+    }
+
+    /// Sets how long to back off after a failed request, and after how many
+    /// consecutive failures the stream gives up. By default, it retries
+    /// forever, from half a second up to 30 seconds apart, with jitter.
+    ///
+    /// Errors that won't go away when retried, like a rejected request,
+    /// also make the stream give up once every Gateway has rejected it.
+    /// When the stream gives up, the processor fails with the last error.
+    pub fn retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+
+    /// Sets the number of transactions to fetch per page.
+    pub fn limit_per_page(mut self, limit_per_page: u32) -> Self {
+        self.limit_per_page = limit_per_page;
+        self
+    }
+
+    /// Sets the buffer capacity of the channel through which transactions are sent to the transaction processor.
+    /// This is the maximum number of transactions that can be buffered before the stream starts to block.
+    /// If the stream is producing transactions faster than the transaction processor can consume them,
+    /// this buffer will fill up.
+    /// You may want to play with this value, based on the performance of the API and the transaction processor.
+    pub fn buffer_capacity(mut self, buffer_capacity: u64) -> Self {
+        self.buffer_capacity = buffer_capacity;
+        self
+    }
+
+    /// Sets the timeout to wait for after each poll of the gateway API when the stream is caught up.
+    /// Tweak this to prevent the stream from polling the API too frequently while there are no transactions to fetch.
+    pub fn caught_up_timeout(mut self, caught_up_timeout: Duration) -> Self {
+        self.caught_up_timeout = caught_up_timeout;
+        self
+    }
+
+    /// Sets whether failed transactions are streamed too. They are skipped
+    /// by default. Event handlers have to opt in to receive their events,
+    /// see [`HandlerRegistry::set_handles_failed_transactions`][crate::event_handler::HandlerRegistry::set_handles_failed_transactions].
+    pub fn include_failed_transactions(
+        mut self,
+        include_failed_transactions: bool,
+    ) -> Self {
+        self.include_failed_transactions = include_failed_transactions;
+        self
+    }
+
+    /// Sets filters that are applied by the Gateway, so that
+    /// transactions which don't match are never fetched.
+    pub fn filters(mut self, filters: GatewayStreamFilters) -> Self {
+        self.filters = filters;
+        self
+    }
+
+    /// Sets the max number of event emitters that transactions are
+    /// filtered on, which is 5 by default. The Gateway only filters on
+    /// one emitter at a time, so each page costs a request per emitter.
+    /// With more emitters than this, the emitter filter isn't applied,
+    /// and all transactions that match the other filters are fetched.
+    pub fn max_emitter_filters(mut self, max_emitter_filters: usize) -> Self {
+        self.max_emitter_filters = max_emitter_filters;
+        self
+    }
+
+    /// Only fetches transactions with events from emitters that handlers
+    /// are registered for, using [`GatewayStreamFilters::from_handler_registry`].
+    /// If that isn't possible, or there are more emitters than
+    /// [`GatewayTransactionStream::max_emitter_filters`], all transactions
+    /// are fetched.
+    ///
+    /// Use this only when the registry holds all handlers up front,
+    /// and the transaction handler doesn't need other transactions.
+    pub fn filter_by_handler_registry(
+        mut self,
+        handler_registry: &HandlerRegistry,
+    ) -> Self {
+        match GatewayStreamFilters::from_handler_registry(handler_registry) {
+            Some(filters) => {
+                self.filters.event_global_emitters =
+                    filters.event_global_emitters;
+            }
+            None => log::warn!(
+                "Can't filter transactions by the handler registry, because \
+                 some handlers match events from any emitter or internal entities"
+            ),
+        }
+        self
+    }
+
+    /// Sets whether the blueprint of each global method emitter is looked up
+    /// using the entity details endpoint, so that it can be used to match
+    /// handlers. This is enabled by default. Blueprints are cached,
+    /// so each entity is only looked up once.
+    pub fn resolve_blueprints(mut self, resolve_blueprints: bool) -> Self {
+        self.resolve_blueprints = resolve_blueprints;
+        self
+    }
+}
+
+/// The max number of entities of which the blueprint is cached.
+const BLUEPRINT_CACHE_CAPACITY: usize = 100_000;
+
+/// The longest a Gateway can ask the stream to wait by default, see
+/// [`GatewayTransactionStream::max_requested_delay`].
+const DEFAULT_MAX_REQUESTED_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// The max number of event emitters that transactions are filtered on
+/// by default, see [`GatewayTransactionStream::max_emitter_filters`].
+const DEFAULT_MAX_EMITTER_FILTERS: usize = 5;
+
+/// A fetcher which is passed to the new task created by the stream.
+struct GatewayFetcher {
+    /// A client for each Gateway, in the order they are tried.
+    api_clients: Vec<GatewayApiClient>,
+    /// The index of the Gateway that requests are sent to.
+    current: usize,
+    retry_policy: RetryPolicy,
+    /// The longest a Gateway can ask to wait before trying again.
+    max_requested_delay: Duration,
+    reporter: StreamReporter,
+    /// The number of requests that failed since the last one that succeeded.
+    failed_attempts: u32,
+    /// The Gateways that rejected a request since then.
+    rejected_by: HashSet<usize>,
+    state_version: u64,
+    limit_per_page: u32,
+    filters: GatewayStreamFilters,
+    caught_up_timeout: Duration,
+    include_failed_transactions: bool,
+    blueprint_cache: Option<BlueprintCache>,
+    bounds: StreamBounds,
+    /// The timestamp to look up the state version to start at for.
+    from_timestamp: Option<DateTime<Utc>>,
+    resolved_from_state_version: StateVersionSlot,
+    reached_state_version: StateVersionSlot,
+    tx: Sender<Transaction>,
+}
+
+impl GatewayFetcher {
+    /// Creates a fetcher using the settings of the stream.
+    pub fn new(
+        stream: &GatewayTransactionStream,
+        http_client: reqwest::Client,
+        tx: Sender<Transaction>,
+    ) -> Self {
+        let api_clients = std::iter::once(&stream.gateway_url)
+            .chain(&stream.fallback_gateway_urls)
+            .map(|gateway_url| stream.api_client(&http_client, gateway_url))
+            .collect();
+        let mut filters = stream.filters.clone();
+        let emitters = filters.event_global_emitters.len();
+        if emitters > stream.max_emitter_filters {
+            log::info!(
+                "Not filtering on {} event emitters, as that takes a request \
+                 per emitter for each page. Fetching all transactions instead",
+                emitters
+            );
+            filters.event_global_emitters.clear();
+        } else if emitters > 0 {
+            log::info!(
+                "Only fetching transactions with events from {} emitters",
+                emitters
+            );
+        }
+        Self {
+            api_clients,
+            current: 0,
+            retry_policy: stream.retry_policy.clone(),
+            max_requested_delay: stream.max_requested_delay,
+            reporter: stream.reporter.clone(),
+            failed_attempts: 0,
+            rejected_by: HashSet::new(),
+            state_version: stream.from_state_version,
+            limit_per_page: stream.limit_per_page,
+            filters,
+            tx,
+            caught_up_timeout: stream.caught_up_timeout,
+            include_failed_transactions: stream.include_failed_transactions,
+            blueprint_cache: stream
+                .resolve_blueprints
+                .then(|| BlueprintCache::new(BLUEPRINT_CACHE_CAPACITY)),
+            bounds: stream.bounds,
+            from_timestamp: stream.from_timestamp,
+            resolved_from_state_version: stream
+                .resolved_from_state_version
+                .clone(),
+            reached_state_version: stream.reached_state_version.clone(),
+        }
+    }
+
+    /// The client for the Gateway that requests are sent to.
+    fn api_client(&self) -> &GatewayApiClient {
+        &self.api_clients[self.current]
+    }
+
+    /// Resets the backoff after a request succeeded.
+    fn succeeded(&mut self) {
+        self.failed_attempts = 0;
+        self.rejected_by.clear();
+    }
+
+    /// Handles a failed request: reports the error, backs off and moves
+    /// on to the next Gateway. Returns the error when the stream should
+    /// give up, because the retry policy allows no more attempts, or
+    /// because every Gateway rejected the request.
+    async fn failed(
+        &mut self,
+        error: anyhow::Error,
+    ) -> Result<(), anyhow::Error> {
+        self.failed_attempts += 1;
+        let kind = client::classify_error(&error);
+        if kind == StreamErrorKind::Permanent {
+            self.rejected_by.insert(self.current);
+        }
+        let give_up = !self.retry_policy.should_retry(self.failed_attempts)
+            || self.rejected_by.len() == self.api_clients.len();
+        let requested_delay =
+            client::requested_delay(&error).map(|requested_delay| {
+                requested_delay.min(self.max_requested_delay)
+            });
+        let retry_in = (!give_up).then(|| {
+            let delay = self.retry_policy.delay(self.failed_attempts);
+            // Without another Gateway to switch to,
+            // the next request waits as long as was asked.
+            match requested_delay {
+                Some(requested_delay) if self.api_clients.len() == 1 => {
+                    delay.max(requested_delay)
+                }
+                _ => delay,
+            }
+        });
+        if let Some(requested_delay) = requested_delay {
+            self.api_client().pause(requested_delay).await;
+        }
+        self.reporter
+            .error(self.api_client().gateway_url(), &error, kind, retry_in)
+            .await;
+        let Some(retry_in) = retry_in else {
+            return Err(error);
+        };
+        if self.api_clients.len() > 1 {
+            self.current = (self.current + 1) % self.api_clients.len();
+            log::info!(
+                "Switching to Gateway {}",
+                self.api_client().gateway_url()
+            );
+        }
+        sleep(retry_in).await;
+        Ok(())
+    }
+
+    /// Looks up the state version to start at, if the stream starts at
+    /// a timestamp. This is the state version of the first transaction
+    /// confirmed at or after it, retrying like any other request.
+    async fn resolve_from_timestamp(&mut self) -> Result<(), anyhow::Error> {
+        let Some(timestamp) = self.from_timestamp else {
+            return Ok(());
+        };
+        let state_version = loop {
+            match self.api_client().state_version_at(timestamp).await {
+                Ok(state_version) => {
+                    self.succeeded();
+                    break state_version;
+                }
+                Err(err) => self.failed(err).await?,
+            }
+        };
+        self.reporter
+            .timestamp_resolved(timestamp, state_version)
+            .await;
+        self.state_version = state_version;
+        self.resolved_from_state_version.set(state_version);
+        Ok(())
+    }
+
+    /// Fetches the next page of transactions and moves past it.
+    async fn next_page(
+        &mut self,
+    ) -> Result<Vec<CommittedTransactionInfo>, anyhow::Error> {
+        if self.filters.event_global_emitters.len() > 1 {
+            return self.next_merged_page().await;
+        }
+        let items = self
+            .api_client()
+            .transactions(
+                self.state_version,
+                None,
+                self.limit_per_page,
+                &self.filters,
+                self.filters
+                    .event_global_emitters
+                    .first()
+                    .map(String::as_str),
+            )
+            .await?;
+        if let Some(last) = items.last() {
+            self.state_version = last.state_version + 1;
+        }
+        Ok(items)
+    }
+
+    /// Fetches the next page of transactions with events from any of
+    /// the emitters in the filters. The Gateway only returns transactions
+    /// with events from all emitters in its filter, so each emitter is
+    /// fetched separately at the same ledger state and the pages are merged.
+    /// The number of emitters is bounded by `max_emitter_filters`.
+    async fn next_merged_page(
+        &mut self,
+    ) -> Result<Vec<CommittedTransactionInfo>, anyhow::Error> {
+        let ledger_state_version =
+            self.api_client().ledger_state().await?.state_version;
+        if ledger_state_version < self.state_version {
+            return Ok(Vec::new());
+        }
+        // Transactions after the end of a full page may be missing
+        // from that page, so only move up to the earliest end.
+        let mut end_state_version = ledger_state_version;
+        let mut items = BTreeMap::new();
+        for emitter in &self.filters.event_global_emitters {
+            let page = self
+                .api_client()
+                .transactions(
+                    self.state_version,
+                    Some(ledger_state_version),
+                    self.limit_per_page,
+                    &self.filters,
+                    Some(emitter),
+                )
+                .await?;
+            if page.len() >= self.limit_per_page as usize {
+                if let Some(last) = page.last() {
+                    end_state_version =
+                        end_state_version.min(last.state_version);
+                }
+            }
+            items.extend(
+                page.into_iter().map(|item| (item.state_version, item)),
+            );
+        }
+        items.split_off(&(end_state_version + 1));
+        self.state_version = end_state_version + 1;
+        Ok(items.into_values().collect())
+    }
+
+    /// Attaches the blueprint to each global method emitter in the
+    /// transactions, looking up the entities that aren't cached yet.
+    /// The entity details endpoint only knows about global entities.
+    async fn resolve_blueprints(
+        &mut self,
+        transactions: &mut [Transaction],
+    ) -> Result<(), anyhow::Error> {
+        let api_client = &self.api_clients[self.current];
+        let Some(blueprint_cache) = &mut self.blueprint_cache else {
+            return Ok(());
+        };
+        let addresses = blueprint_cache.unknown_addresses(transactions, true);
+        if !addresses.is_empty() {
+            let blueprints = api_client.entity_blueprints(&addresses).await?;
+            blueprint_cache.extend(addresses, blueprints);
+        }
+        blueprint_cache.attach(transactions);
+        Ok(())
+    }
+
+    /// Fetches transactions from the gateway and sends them to the transaction processor.
+    /// Returns the error it gave up on, if any.
+    async fn run(&mut self) -> Result<(), anyhow::Error> {
+        self.resolve_from_timestamp().await?;
+        // The ledger state version that was looked up after an empty
+        // page, once the ledger has passed the bounds of the stream.
+        let mut caught_up_tip = None;
+        loop {
+            let items = match self.next_page().await {
+                Ok(items) => items,
+                Err(err) => {
+                    self.failed(err).await?;
+                    continue;
+                }
+            };
+            self.succeeded();
+            if items.is_empty() {
+                // This page was fetched after the ledger state was looked
+                // up, so no transactions up to it were missed.
+                if let Some(tip) = caught_up_tip {
+                    self.reached_state_version.set(self.bounds.reached_at(tip));
+                    return Ok(());
+                }
+                if self.bounds.is_bounded() {
+                    match self.api_client().ledger_state().await {
+                        Ok(ledger_state)
+                            if self.bounds.is_passed_by(
+                                ledger_state.state_version,
+                                ledger_state.proposer_round_timestamp,
+                            ) =>
+                        {
+                            caught_up_tip = Some(ledger_state.state_version);
+                            continue;
+                        }
+                        Ok(_) => {}
+                        Err(err) => {
+                            self.failed(err).await?;
+                            continue;
+                        }
+                    }
+                }
+                sleep(self.caught_up_timeout).await;
+            }
+            caught_up_tip = None;
+            let mut transactions: Vec<Transaction> = Vec::new();
+            // The state version the stream reached, once it's finished.
+            let mut finished_at = None;
+            for transaction in items.into_iter().map(Transaction::from) {
+                if !self.bounds.contains(&transaction) {
+                    finished_at =
+                        Some(transaction.state_version.saturating_sub(1));
+                    break;
+                }
+                if self.bounds.is_last(&transaction) {
+                    finished_at = Some(transaction.state_version);
+                }
+                if self.include_failed_transactions
+                    || transaction.status.is_success()
+                {
+                    transactions.push(transaction);
+                }
+                if finished_at.is_some() {
+                    break;
+                }
+            }
+            while let Err(err) =
+                self.resolve_blueprints(&mut transactions).await
+            {
+                self.failed(err).await?;
+            }
+            self.succeeded();
+            for transaction in transactions {
+                // Stop fetching if the receiving end is closed
+                if self.tx.send(transaction).await.is_err() {
+                    return Ok(());
+                }
+            }
+            // Dropping the sender ends the stream.
+            if let Some(state_version) = finished_at {
+                self.reached_state_version.set(state_version);
+                return Ok(());
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl TransactionStream for GatewayTransactionStream {
+    async fn start(&mut self) -> Result<Receiver<Transaction>, anyhow::Error> {
+        let (tx, rx) =
+            tokio::sync::mpsc::channel(self.buffer_capacity as usize);
+        let http_client = self.http_client.build()?;
+        self.reached_state_version = StateVersionSlot::default();
+        self.resolved_from_state_version = StateVersionSlot::default();
+        // The timestamp is looked up by the fetcher, so stopping
+        // the stream also stops retrying the lookup.
+        let mut fetcher = GatewayFetcher::new(self, http_client, tx);
+        let error = self.error.clone();
+        // The error is stored before the fetcher drops the sender.
+        let handle = tokio::spawn(async move {
+            if let Err(err) = fetcher.run().await {
+                error.set(err);
+            }
+        });
+        self.handle = Some(handle);
+        Ok(rx)
+    }
+
+    async fn stop(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            handle.abort();
+        }
+    }
+
+    fn resume_from_state_version(&mut self, state_version: u64) {
+        self.from_state_version = state_version;
+        self.from_timestamp = None;
+    }
+
+    fn set_logger(&mut self, logger: Arc<RwLock<Box<dyn Logger>>>) {
+        self.reporter.logger = Some(logger);
+    }
+
+    fn take_error(&mut self) -> Option<anyhow::Error> {
+        self.error.take()
+    }
+
+    fn reached_state_version(&self) -> Option<u64> {
+        self.reached_state_version.get()
+    }
+}
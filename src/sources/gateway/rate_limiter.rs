@@ -0,0 +1,102 @@
+//! Limits the rate of requests to a Gateway.
+
+use std::time::Duration;
+use tokio::{sync::Mutex, time::Instant};
+
+/// How long requests are held back when asked
+/// for a delay that's too long to represent.
+const MAX_PAUSE: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// A token bucket that allows `burst` requests at once, and refills
+/// at `requests_per_second`. Requests can also be paused for a while,
+/// when the Gateway asks to slow down.
+#[derive(Debug)]
+pub(crate) struct RateLimiter {
+    limit: Option<RateLimit>,
+    bucket: Mutex<Bucket>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RateLimit {
+    requests_per_second: f64,
+    burst: f64,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+    paused_until: Option<Instant>,
+}
+
+impl RateLimiter {
+    /// Creates a limiter that only holds back requests while paused.
+    pub(crate) fn unlimited() -> Self {
+        Self {
+            limit: None,
+            bucket: Mutex::new(Bucket {
+                tokens: 0.0,
+                updated: Instant::now(),
+                paused_until: None,
+            }),
+        }
+    }
+
+    pub(crate) fn new(requests_per_second: f64, burst: u32) -> Self {
+        let burst = f64::from(burst.max(1));
+        Self {
+            limit: Some(RateLimit {
+                requests_per_second: requests_per_second.max(f64::EPSILON),
+                burst,
+            }),
+            bucket: Mutex::new(Bucket {
+                tokens: burst,
+                updated: Instant::now(),
+                paused_until: None,
+            }),
+        }
+    }
+
+    /// Waits until a request may be sent. Waiting requests are let
+    /// through in the order they arrived.
+    pub(crate) async fn acquire(&self) {
+        let mut bucket = self.bucket.lock().await;
+        if let Some(paused_until) = bucket.paused_until.take() {
+            tokio::time::sleep_until(paused_until).await;
+        }
+        let Some(limit) = self.limit else {
+            return;
+        };
+        let now = Instant::now();
+        let elapsed = now.saturating_duration_since(bucket.updated);
+        bucket.tokens = (bucket.tokens
+            + elapsed.as_secs_f64() * limit.requests_per_second)
+            .min(limit.burst);
+        bucket.updated = now;
+        if bucket.tokens < 1.0 {
+            let wait = Duration::from_secs_f64(
+                (1.0 - bucket.tokens) / limit.requests_per_second,
+            );
+            tokio::time::sleep(wait).await;
+            bucket.tokens = 1.0;
+            bucket.updated = now + wait;
+        }
+        bucket.tokens -= 1.0;
+    }
+
+    /// Holds back all requests for `duration`, after which requests
+    /// start again at the normal rate, without a burst.
+    pub(crate) async fn pause(&self, duration: Duration) {
+        let mut bucket = self.bucket.lock().await;
+        let now = Instant::now();
+        // A Gateway can ask for any delay, which may not fit in an instant.
+        let until = now.checked_add(duration).unwrap_or(now + MAX_PAUSE);
+        bucket.paused_until = Some(
+            bucket
+                .paused_until
+                .map_or(until, |paused_until| paused_until.max(until)),
+        );
+        bucket.tokens = 0.0;
+        bucket.updated = until;
+    }
+}
@@ -0,0 +1,269 @@
+//! A transaction stream that backfills from one stream and then
+//! continues live from another.
+
+use crate::{
+    logger::Logger,
+    models::Transaction,
+    stream::{StreamErrorSlot, TransactionStream},
+};
+use async_trait::async_trait;
+use std::{fmt::Debug, sync::Arc};
+use tokio::sync::{
+    mpsc::{Receiver, Sender},
+    Mutex, RwLock,
+};
+
+/// A stream that first drains a finite backfill stream, like a
+/// [`DatabaseTransactionStream`][crate::sources::database::DatabaseTransactionStream]
+/// with `stop_when_caught_up(true)` or a file stream, and then switches
+/// to a live stream, like a
+/// [`GatewayTransactionStream`][crate::sources::gateway::GatewayTransactionStream].
+///
+/// The live stream is started at the state version right after the one
+/// the backfill reached, see [`TransactionStream::reached_state_version`].
+/// For a backfill which doesn't know it, this is the state version right
+/// after its last transaction. Every transaction is checked against the
+/// state version of the one before it: transactions that were already
+/// emitted are dropped, so there are no duplicates around the switch.
+/// With [`HybridTransactionStream::strict_continuity`], the stream also
+/// fails when a state version is skipped.
+///
+/// When the backfill stream gives up on its source, the stream fails
+/// with its error instead of switching to the live stream. It also fails
+/// when the backfill ended without a transaction or a state version it
+/// reached, and no `from_state_version` is set, as there is no state
+/// version to continue live at.
+#[derive(Debug)]
+pub struct HybridTransactionStream<BACKFILL, LIVE>
+where
+    BACKFILL: TransactionStream + Send + 'static,
+    LIVE: TransactionStream + Send + 'static,
+{
+    backfill: Arc<Mutex<BACKFILL>>,
+    live: Arc<Mutex<LIVE>>,
+    from_state_version: Option<u64>,
+    buffer_capacity: u64,
+    strict_continuity: bool,
+    error: StreamErrorSlot,
+    join_handle: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl<BACKFILL, LIVE> HybridTransactionStream<BACKFILL, LIVE>
+where
+    BACKFILL: TransactionStream + Send + 'static,
+    LIVE: TransactionStream + Send + 'static,
+{
+    /// Creates a new stream from a backfill stream and a live stream. The
+    /// backfill stream must end once it has caught up, otherwise
+    /// the live stream is never started.
+    pub fn new(backfill: BACKFILL, live: LIVE) -> Self {
+        Self {
+            backfill: Arc::new(Mutex::new(backfill)),
+            live: Arc::new(Mutex::new(live)),
+            from_state_version: None,
+            buffer_capacity: 1000,
+            strict_continuity: false,
+            error: StreamErrorSlot::default(),
+            join_handle: None,
+        }
+    }
+
+    /// Sets the state version to start at, for both streams.
+    /// By default, the backfill stream starts at the state version
+    /// it was configured with.
+    pub fn from_state_version(mut self, state_version: u64) -> Self {
+        self.from_state_version = Some(state_version);
+        self
+    }
+
+    /// Sets the buffer capacity of the channel through which
+    /// transactions are sent to the transaction processor.
+    pub fn buffer_capacity(mut self, buffer_capacity: u64) -> Self {
+        self.buffer_capacity = buffer_capacity;
+        self
+    }
+
+    /// Sets whether the stream fails when a transaction doesn't directly
+    /// follow the one before it. Only enable this when both streams emit
+    /// every transaction, so with all transaction kinds and failed
+    /// transactions included. Disabled by default.
+    pub fn strict_continuity(mut self, strict_continuity: bool) -> Self {
+        self.strict_continuity = strict_continuity;
+        self
+    }
+}
+
+/// Forwards transactions while checking that their state versions
+/// follow each other.
+struct ContinuityCheck {
+    last_state_version: Option<u64>,
+    strict: bool,
+}
+
+impl ContinuityCheck {
+    /// Sends the transaction if it comes after the last one. Returns
+    /// false if the stream should end because the receiver was dropped,
+    /// or an error if a state version was skipped in strict mode.
+    async fn forward(
+        &mut self,
+        transaction: Transaction,
+        tx: &Sender<Transaction>,
+    ) -> Result<bool, anyhow::Error> {
+        if let Some(last_state_version) = self.last_state_version {
+            if transaction.state_version <= last_state_version {
+                log::debug!(
+                    "Dropping transaction at state version {}, which was already emitted",
+                    transaction.state_version
+                );
+                return Ok(true);
+            }
+            if self.strict
+                && transaction.state_version != last_state_version + 1
+            {
+                return Err(anyhow::anyhow!(
+                    "Expected state version {} after {}, but received {}",
+                    last_state_version + 1,
+                    last_state_version,
+                    transaction.state_version
+                ));
+            }
+        }
+        self.last_state_version = Some(transaction.state_version);
+        Ok(tx.send(transaction).await.is_ok())
+    }
+
+    /// Moves past the state version a stream reached, so the transactions
+    /// up to it are considered emitted.
+    fn reached(&mut self, state_version: u64) {
+        self.last_state_version = Some(
+            self.last_state_version
+                .map_or(state_version, |last| last.max(state_version)),
+        );
+    }
+
+    /// The state version the live stream should start at.
+    fn next_state_version(&self) -> Option<u64> {
+        self.last_state_version
+            .map(|state_version| state_version + 1)
+    }
+}
+
+#[async_trait]
+impl<BACKFILL, LIVE> TransactionStream
+    for HybridTransactionStream<BACKFILL, LIVE>
+where
+    BACKFILL: TransactionStream + Send + 'static,
+    LIVE: TransactionStream + Send + 'static,
+{
+    async fn start(&mut self) -> Result<Receiver<Transaction>, anyhow::Error> {
+        let (tx, rx) =
+            tokio::sync::mpsc::channel(self.buffer_capacity as usize);
+        let mut backfill_receiver = {
+            let mut backfill = self.backfill.lock().await;
+            if let Some(state_version) = self.from_state_version {
+                backfill.resume_from_state_version(state_version);
+            }
+            backfill.start().await?
+        };
+        let backfill = self.backfill.clone();
+        let live = self.live.clone();
+        let mut continuity_check = ContinuityCheck {
+            last_state_version: self
+                .from_state_version
+                .and_then(|state_version| state_version.checked_sub(1)),
+            strict: self.strict_continuity,
+        };
+        let error = self.error.clone();
+        let handle = tokio::spawn(async move {
+            // The sender is only borrowed, so the error
+            // is stored before the sender is dropped.
+            let result: Result<(), anyhow::Error> = async {
+                while let Some(transaction) = backfill_receiver.recv().await {
+                    if !continuity_check.forward(transaction, &tx).await? {
+                        return Ok(());
+                    }
+                }
+                {
+                    let mut backfill = backfill.lock().await;
+                    backfill.stop().await;
+                    if let Some(err) = backfill.take_error() {
+                        return Err(err.context("The backfill stream failed"));
+                    }
+                    if let Some(state_version) =
+                        backfill.reached_state_version()
+                    {
+                        continuity_check.reached(state_version);
+                    }
+                }
+                let Some(next_state_version) =
+                    continuity_check.next_state_version()
+                else {
+                    return Err(anyhow::anyhow!(
+                        "The backfill stream ended without a transaction, \
+                         so there is no state version to continue live at. \
+                         Set one with from_state_version"
+                    ));
+                };
+                let live_receiver = {
+                    let mut live = live.lock().await;
+                    live.resume_from_state_version(next_state_version);
+                    live.start().await
+                };
+                let mut live_receiver = live_receiver.map_err(|err| {
+                    err.context("Failed to start the live stream")
+                })?;
+                log::info!(
+                    "Backfill finished, continuing live from state version {}",
+                    next_state_version
+                );
+                while let Some(transaction) = live_receiver.recv().await {
+                    if !continuity_check.forward(transaction, &tx).await? {
+                        return Ok(());
+                    }
+                }
+                match live.lock().await.take_error() {
+                    Some(err) => Err(err),
+                    None => Ok(()),
+                }
+            }
+            .await;
+            if let Err(err) = result {
+                error.set(err);
+            }
+        });
+        self.join_handle = Some(handle);
+        Ok(rx)
+    }
+
+    async fn stop(&mut self) {
+        if let Some(handle) = self.join_handle.take() {
+            handle.abort();
+        }
+        self.backfill.lock().await.stop().await;
+        self.live.lock().await.stop().await;
+    }
+
+    fn resume_from_state_version(&mut self, state_version: u64) {
+        self.from_state_version = Some(state_version);
+    }
+
+    // Like resuming, this happens before the stream is started,
+    // so the locks are never held here.
+    fn set_logger(&mut self, logger: Arc<RwLock<Box<dyn Logger>>>) {
+        if let Ok(mut backfill) = self.backfill.try_lock() {
+            backfill.set_logger(logger.clone());
+        }
+        if let Ok(mut live) = self.live.try_lock() {
+            live.set_logger(logger);
+        }
+    }
+
+    fn take_error(&mut self) -> Option<anyhow::Error> {
+        self.error.take()
+    }
+
+    // The live stream ends the stream, and it isn't locked once it ended.
+    fn reached_state_version(&self) -> Option<u64> {
+        self.live.try_lock().ok()?.reached_state_version()
+    }
+}
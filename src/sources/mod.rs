@@ -6,6 +6,14 @@
 //! to only include the implementations that are needed for your use case,
 //! because this allows you to skip some optional dependencies.
 
+#[cfg(feature = "file")]
+pub mod archive;
+#[cfg(any(feature = "database", feature = "gateway"))]
+mod blueprint_cache;
+#[cfg(any(feature = "database", feature = "gateway"))]
+mod bounds;
+#[cfg(feature = "channel")]
+pub mod broadcast;
 #[cfg(feature = "channel")]
 pub mod channel;
 #[cfg(feature = "database")]
@@ -14,3 +22,8 @@ pub mod database;
 pub mod file;
 #[cfg(feature = "gateway")]
 pub mod gateway;
+pub mod hybrid;
+#[cfg(feature = "file")]
+pub mod recording;
+#[cfg(any(feature = "database", feature = "gateway"))]
+mod reporter;
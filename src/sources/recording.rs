@@ -0,0 +1,364 @@
+//! A transaction stream that records the transactions of another stream
+//! to an archive, which can be replayed with a
+//! [`FileTransactionStream`][crate::sources::file::FileTransactionStream].
+
+use crate::{
+    logger::Logger,
+    models::Transaction,
+    sources::{
+        archive,
+        file::{Compression, FileFormat, SourceFile},
+    },
+    stream::{StreamErrorSlot, TransactionStream},
+};
+use async_trait::async_trait;
+use std::{
+    fs::{File, OpenOptions},
+    io::{BufWriter, Write},
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+use tokio::sync::{
+    mpsc::{error::TryRecvError, Receiver},
+    RwLock,
+};
+
+const BUFFER_CAPACITY: usize = 1000;
+
+/// A stream that passes on the transactions of another stream, while
+/// writing each of them to an archive file. This makes it possible
+/// to capture a range of transactions from any source, and replay it
+/// later on with a [`FileTransactionStream`][crate::sources::file::FileTransactionStream],
+/// for example as a test fixture.
+///
+/// Transactions are written in the format of [`Transaction`], so they are
+/// replayed exactly as they were received, including the binary SBOR data
+/// of their events. The format depends on the file extension:
+/// newline-delimited JSON for `.ndjson` or `.jsonl`, or a binary
+/// [`archive`][crate::sources::archive] for `.bin`, which is more compact
+/// and faster to replay. Either can be followed by `.gz` or `.zst` to
+/// compress it. An uncompressed binary archive is indexed as well.
+///
+/// The archive is flushed whenever the wrapped stream has no transactions
+/// ready, and finished when the stream ends or is stopped. If writing
+/// fails, the stream fails with the error, so a recording never
+/// silently has gaps.
+#[derive(Debug)]
+pub struct RecordingStream<S> {
+    stream: S,
+    path: PathBuf,
+    append: bool,
+    error: StreamErrorSlot,
+    join_handle: Option<tokio::task::JoinHandle<()>>,
+    writer_handle: Option<tokio::task::JoinHandle<()>>,
+}
+
+impl<S: TransactionStream + Send> RecordingStream<S> {
+    /// Creates a stream that records the transactions of `stream`
+    /// to the file at `path`.
+    pub fn new(stream: S, path: impl Into<PathBuf>) -> Self {
+        Self {
+            stream,
+            path: path.into(),
+            append: false,
+            error: StreamErrorSlot::default(),
+            join_handle: None,
+            writer_handle: None,
+        }
+    }
+
+    /// Sets whether transactions are appended to an existing file,
+    /// for example when resuming a recording from a checkpoint.
+    /// By default, an existing file is overwritten.
+    ///
+    /// Transactions up to the last one in the file are passed on, but
+    /// not recorded again, so the stream can resume from a checkpoint
+    /// before the end of the recording. Finding the last transaction
+    /// means reading the whole file, unless it is an indexed archive.
+    /// A transaction that was only partly written at the end of the file
+    /// is removed first, except from a compressed file, which can't be
+    /// appended to then.
+    pub fn append(mut self, append: bool) -> Self {
+        self.append = append;
+        self
+    }
+}
+
+/// The file that is written to, compressed if needed.
+enum Output {
+    Plain(BufWriter<File>),
+    Gzip(flate2::write::GzEncoder<BufWriter<File>>),
+    Zstd(zstd::stream::write::Encoder<'static, BufWriter<File>>),
+}
+
+impl Output {
+    fn writer(&mut self) -> &mut dyn Write {
+        match self {
+            Output::Plain(writer) => writer,
+            Output::Gzip(writer) => writer,
+            Output::Zstd(writer) => writer,
+        }
+    }
+
+    /// Completes the compressed stream and flushes the file.
+    fn finish(self) -> std::io::Result<()> {
+        let mut file = match self {
+            Output::Plain(file) => file,
+            Output::Gzip(writer) => writer.finish()?,
+            Output::Zstd(writer) => writer.finish()?,
+        };
+        file.flush()
+    }
+}
+
+/// Writes transactions to an archive file and its index.
+struct ArchiveWriter {
+    output: Output,
+    binary: bool,
+    index: Option<BufWriter<File>>,
+    /// The offset in the file of the next transaction. This is
+    /// only kept track of for an indexed archive.
+    offset: u64,
+    written: u64,
+    /// The state version of the last transaction that was
+    /// already in the file, when appending to it.
+    last_state_version: Option<u64>,
+}
+
+impl ArchiveWriter {
+    /// Opens the file at `path` to record to, and when appending,
+    /// finds the last transaction that was recorded before. A transaction
+    /// that was only partly written, for example because the process was
+    /// killed, is removed, or for a compressed file, appending fails.
+    fn open(path: &Path, append: bool) -> Result<Self, anyhow::Error> {
+        let source_file = match SourceFile::new(path.to_path_buf()) {
+            Some(
+                source_file @ SourceFile {
+                    format: FileFormat::JsonLines | FileFormat::Binary,
+                    ..
+                },
+            ) => source_file,
+            _ => {
+                return Err(anyhow::anyhow!(
+                    "Can't record to {}, the file must end in .ndjson, .jsonl or .bin, optionally followed by .gz or .zst",
+                    path.display()
+                ))
+            }
+        };
+        let open = |path: &Path| {
+            OpenOptions::new()
+                .create(true)
+                .write(true)
+                .append(append)
+                .truncate(!append)
+                .open(path)
+        };
+        let file = open(path)?;
+        let mut length = file.metadata()?.len();
+        let last_state_version = if length > 0 {
+            let tail = source_file.tail()?;
+            if tail.partial {
+                // A compressed file can't be cut off in the middle
+                // of its compressed data.
+                if source_file.compression != Compression::None {
+                    return Err(anyhow::anyhow!(
+                        "Can't append to {}, it ends in a partially written transaction",
+                        path.display()
+                    ));
+                }
+                log::warn!(
+                    "Removing a partially written transaction from the end of {}",
+                    path.display()
+                );
+                file.set_len(tail.end)?;
+                length = tail.end;
+            }
+            tail.last_state_version
+        } else {
+            None
+        };
+        if let Some(state_version) = last_state_version {
+            log::info!(
+                "Appending to {} after state version {}",
+                path.display(),
+                state_version
+            );
+        }
+        let file = BufWriter::new(file);
+        let binary = source_file.format == FileFormat::Binary;
+        let index = if source_file.is_indexable() {
+            let index = open(&archive::index_path(path))?;
+            // Entries that were cut off, or that point past the last
+            // complete transaction, are removed before appending.
+            let entries = archive::read_index(path)?
+                .unwrap_or_default()
+                .into_iter()
+                .take_while(|entry| entry.offset < length)
+                .count() as u64;
+            index.set_len(entries * archive::INDEX_ENTRY_LENGTH)?;
+            Some(BufWriter::new(index))
+        } else {
+            None
+        };
+        let mut output = match source_file.compression {
+            Compression::None => Output::Plain(file),
+            Compression::Gzip => Output::Gzip(flate2::write::GzEncoder::new(
+                file,
+                flate2::Compression::default(),
+            )),
+            Compression::Zstd => {
+                Output::Zstd(zstd::stream::write::Encoder::new(file, 0)?)
+            }
+        };
+        // Appending to a compressed file starts a new gzip member
+        // or zstd frame, which is read as part of the same file,
+        // so the header is only written at the very start.
+        if binary && length == 0 {
+            archive::write_header(output.writer())?;
+        }
+        Ok(ArchiveWriter {
+            output,
+            binary,
+            index,
+            offset: length.max(archive::HEADER_LENGTH),
+            written: 0,
+            last_state_version,
+        })
+    }
+
+    fn write(&mut self, transaction: Transaction) -> anyhow::Result<()> {
+        let state_version = transaction.state_version;
+        // The transaction was recorded before.
+        if self.last_state_version.is_some_and(|last_state_version| {
+            state_version <= last_state_version
+        }) {
+            return Ok(());
+        }
+        let record = if self.binary {
+            archive::encode_transaction(transaction)?
+        } else {
+            let mut line = serde_json::to_vec(&transaction)?;
+            line.push(b'\n');
+            line
+        };
+        if let Some(index) = &mut self.index {
+            if self.written % archive::INDEX_INTERVAL == 0 {
+                let entry = archive::IndexEntry {
+                    state_version,
+                    offset: self.offset,
+                };
+                index.write_all(&entry.to_bytes())?;
+            }
+            self.offset += record.len() as u64;
+        }
+        self.output.writer().write_all(&record)?;
+        self.written += 1;
+        Ok(())
+    }
+
+    /// Writes out everything that is buffered, so that it can be read
+    /// even if the archive is never finished. The index is flushed
+    /// last, so it never points past the end of the archive.
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.output.writer().flush()?;
+        if let Some(index) = &mut self.index {
+            index.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Writes transactions as they come in, until the sender is dropped.
+    fn record(
+        mut self,
+        transactions: &mut Receiver<Transaction>,
+    ) -> anyhow::Result<()> {
+        loop {
+            let transaction = match transactions.try_recv() {
+                Ok(transaction) => transaction,
+                Err(TryRecvError::Empty) => {
+                    self.flush()?;
+                    match transactions.blocking_recv() {
+                        Some(transaction) => transaction,
+                        None => break,
+                    }
+                }
+                Err(TryRecvError::Disconnected) => break,
+            };
+            self.write(transaction)?;
+        }
+        self.output.finish()?;
+        if let Some(mut index) = self.index {
+            index.flush()?;
+        }
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<S: TransactionStream + Send> TransactionStream for RecordingStream<S> {
+    async fn start(&mut self) -> Result<Receiver<Transaction>, anyhow::Error> {
+        let (path, append) = (self.path.clone(), self.append);
+        let writer = tokio::task::spawn_blocking(move || {
+            ArchiveWriter::open(&path, append)
+        })
+        .await??;
+        let mut receiver = self.stream.start().await?;
+        let (tx, rx) = tokio::sync::mpsc::channel(BUFFER_CAPACITY);
+        let (archive_tx, mut archive_rx) =
+            tokio::sync::mpsc::channel(BUFFER_CAPACITY);
+        let path = self.path.clone();
+        let error = self.error.clone();
+        // Writing is blocking, so it's done on a thread where that's allowed.
+        self.writer_handle = Some(tokio::task::spawn_blocking(move || {
+            // The error is stored before the receiver is dropped,
+            // which ends the stream.
+            if let Err(err) = writer.record(&mut archive_rx) {
+                error.set(err.context(format!(
+                    "Error writing recording to {}",
+                    path.display()
+                )));
+            }
+        }));
+        self.join_handle = Some(tokio::spawn(async move {
+            while let Some(transaction) = receiver.recv().await {
+                // The writer only stops early when writing failed.
+                if archive_tx.send(transaction.clone()).await.is_err() {
+                    return;
+                }
+                if tx.send(transaction).await.is_err() {
+                    return;
+                }
+            }
+        }));
+        Ok(rx)
+    }
+
+    async fn stop(&mut self) {
+        if let Some(handle) = self.join_handle.take() {
+            handle.abort();
+            let _ = handle.await;
+        }
+        self.stream.stop().await;
+        // With the forwarding task gone, the writer finishes
+        // the lines it has left and completes the archive.
+        if let Some(handle) = self.writer_handle.take() {
+            let _ = handle.await;
+        }
+    }
+
+    fn resume_from_state_version(&mut self, state_version: u64) {
+        self.stream.resume_from_state_version(state_version);
+    }
+
+    fn set_logger(&mut self, logger: Arc<RwLock<Box<dyn Logger>>>) {
+        self.stream.set_logger(logger);
+    }
+
+    fn take_error(&mut self) -> Option<anyhow::Error> {
+        self.error.take().or_else(|| self.stream.take_error())
+    }
+
+    fn reached_state_version(&self) -> Option<u64> {
+        self.stream.reached_state_version()
+    }
+}
@@ -0,0 +1,79 @@
+//! Reports what happens in a source to the logger of the processor,
+//! shared by the sources that support it.
+
+use crate::{logger::Logger, stream::StreamErrorKind};
+use chrono::{DateTime, Utc};
+use std::{fmt, sync::Arc, time::Duration};
+use tokio::sync::RwLock;
+
+/// Reports to the logger of the processor, or to the log
+/// when the stream isn't run by a processor with a logger.
+#[derive(Clone, Default)]
+pub(crate) struct StreamReporter {
+    pub logger: Option<Arc<RwLock<Box<dyn Logger>>>>,
+}
+
+impl fmt::Debug for StreamReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StreamReporter")
+            .field("has_logger", &self.logger.is_some())
+            .finish()
+    }
+}
+
+impl StreamReporter {
+    /// Reports an error fetching from `source`, see [`Logger::stream_error`].
+    pub async fn error(
+        &self,
+        source: &str,
+        error: &anyhow::Error,
+        kind: StreamErrorKind,
+        retry_in: Option<Duration>,
+    ) {
+        match (&self.logger, retry_in) {
+            (Some(logger), _) => {
+                logger
+                    .write()
+                    .await
+                    .stream_error(source, error, kind, retry_in)
+                    .await;
+            }
+            (None, Some(retry_in)) => log::warn!(
+                "{:?} error fetching from {}: {:?}. Retrying in {:.1} seconds",
+                kind,
+                source,
+                error,
+                retry_in.as_secs_f32()
+            ),
+            (None, None) => log::error!(
+                "{:?} error fetching from {}: {:?}. Giving up",
+                kind,
+                source,
+                error
+            ),
+        }
+    }
+
+    /// Reports the state version that the timestamp to start at resolved
+    /// to, see [`Logger::stream_timestamp_resolved`].
+    pub async fn timestamp_resolved(
+        &self,
+        timestamp: DateTime<Utc>,
+        state_version: u64,
+    ) {
+        match &self.logger {
+            Some(logger) => {
+                logger
+                    .write()
+                    .await
+                    .stream_timestamp_resolved(timestamp, state_version)
+                    .await;
+            }
+            None => log::info!(
+                "Resolved timestamp {} to state version {}",
+                timestamp,
+                state_version
+            ),
+        }
+    }
+}
@@ -1,10 +1,10 @@
 //! Has a trait that abstracts a stream of transactions coming
 //! from any source, like a gateway, database, or file.
 
-use crate::models::Transaction;
+use crate::{logger::Logger, models::Transaction};
 use async_trait::async_trait;
-use std::fmt::Debug;
-use tokio::sync::mpsc::Receiver;
+use std::{fmt::Debug, sync::Arc};
+use tokio::sync::{mpsc::Receiver, RwLock};
 
 /// A trait that abstracts a stream of transactions coming
 /// from any source, like a gateway, database, or file.
@@ -15,7 +15,9 @@ use tokio::sync::mpsc::Receiver;
 /// If the stream is finished, which can happen when processing
 /// a finite source of transactions such as a file, the stream
 /// should simply close the channel and the processor will exit
-/// gracefully.
+/// gracefully. If the stream gives up on its source instead, it should
+/// store the error in a [`StreamErrorSlot`] before closing the channel,
+/// so the processor fails with it, see [`TransactionStream::take_error`].
 ///
 /// If a stream, like a Gateway stream, is caught up to the latest state, it may be
 /// possible that there are no transactions to push to the channel for a while.
@@ -39,4 +41,89 @@ pub trait TransactionStream: Debug {
 
     // Explicitly stop the stream
     async fn stop(&mut self);
+
+    /// Tells the stream to start at `state_version` instead of the
+    /// state version it was configured with. This is called by the
+    /// [`TransactionStreamProcessor`][crate::processor::TransactionStreamProcessor]
+    /// before [`TransactionStream::start`] when a
+    /// [`CheckpointStore`][crate::checkpoints::CheckpointStore] holds a checkpoint.
+    ///
+    /// Streams that can't seek, like a channel, may ignore this.
+    fn resume_from_state_version(&mut self, _state_version: u64) {}
+
+    /// Gives the stream the logger of the processor, so it can report
+    /// errors from its source through [`Logger::stream_error`]. This is
+    /// called by the processor before [`TransactionStream::start`].
+    ///
+    /// Streams that wrap other streams should pass it on to them.
+    fn set_logger(&mut self, _logger: Arc<RwLock<Box<dyn Logger>>>) {}
+
+    /// Returns the error that ended the stream, if it gave up on its
+    /// source instead of reaching its end. The processor calls this when
+    /// the channel is closed, and fails with the error if there is one.
+    ///
+    /// Streams that wrap other streams should pass it on to them.
+    fn take_error(&mut self) -> Option<anyhow::Error> {
+        None
+    }
+
+    /// Returns the state version up to which the stream has read its
+    /// source, once it ended. This can be past the last transaction it
+    /// emitted, when the transactions after it were filtered out or when
+    /// the stream ended at a bound. Returns `None` if it isn't known.
+    ///
+    /// Streams that wrap other streams should pass it on to them.
+    fn reached_state_version(&self) -> Option<u64> {
+        None
+    }
+}
+
+/// Holds the error that ended a stream. The task which feeds the channel
+/// of the stream sets it before closing the channel, and the stream
+/// returns it from [`TransactionStream::take_error`].
+#[derive(Debug, Clone, Default)]
+pub struct StreamErrorSlot(Arc<std::sync::Mutex<Option<anyhow::Error>>>);
+
+impl StreamErrorSlot {
+    /// Stores the error, unless an earlier error was stored already.
+    pub fn set(&self, error: anyhow::Error) {
+        self.0.lock().unwrap().get_or_insert(error);
+    }
+
+    /// Takes the error out of the slot.
+    pub fn take(&self) -> Option<anyhow::Error> {
+        self.0.lock().unwrap().take()
+    }
+}
+
+/// Holds a state version that the task which feeds the channel of a
+/// stream finds out about, like the one it reached, see
+/// [`TransactionStream::reached_state_version`]. The task sets it
+/// before closing the channel.
+#[derive(Debug, Clone, Default)]
+pub struct StateVersionSlot(Arc<std::sync::Mutex<Option<u64>>>);
+
+impl StateVersionSlot {
+    pub fn set(&self, state_version: u64) {
+        *self.0.lock().unwrap() = Some(state_version);
+    }
+
+    pub fn get(&self) -> Option<u64> {
+        *self.0.lock().unwrap()
+    }
+}
+
+/// How an error a stream ran into while fetching from its source
+/// is classified, which determines how the stream deals with it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamErrorKind {
+    /// The source is temporarily unavailable, like a network error or
+    /// a server error. The stream backs off and tries again.
+    Transient,
+    /// The source is rate limiting the stream.
+    /// The stream backs off and tries again.
+    RateLimited,
+    /// The request was rejected and won't succeed when tried again
+    /// against the same source, like a request with an invalid filter.
+    Permanent,
 }
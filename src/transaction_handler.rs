@@ -63,6 +63,15 @@ async fn transaction_handler_name(
 ```
 Now, we can simply pass in the handler to the [`TransactionStreamProcessor`][crate::processor::TransactionStreamProcessor].
 It is now secretly a struct that implements the [`TransactionHandler`] trait.
+
+## Batches
+
+When writing to a database, it is often much faster to commit many transactions
+at once. A [`BatchTransactionHandler`] receives a batch of consecutive transactions
+instead of a single one. The [`TransactionStreamProcessor`][crate::processor::TransactionStreamProcessor]
+collects transactions until the batch is full or the first transaction in it has waited
+for the linger time, and then calls the handler. A `TransactionRetryError` retries the
+whole batch, and the checkpoint is only moved once the whole batch is handled.
 */
 
 /// A trait that defines a transaction handler.
@@ -79,7 +88,7 @@ use async_trait::async_trait;
 
 #[allow(non_camel_case_types)]
 #[async_trait]
-pub trait TransactionHandler<STATE>: 'static {
+pub trait TransactionHandler<STATE>: Send + Sync + 'static {
     async fn handle(
         &self,
         input: TransactionHandlerContext<'_, STATE>,
@@ -95,3 +104,40 @@ pub struct TransactionHandlerContext<'a, STATE> {
     pub event_processor: &'a mut EventProcessor<'a>,
     pub handler_registry: &'a mut HandlerRegistry,
 }
+
+/// A trait that defines a transaction handler which handles
+/// a batch of transactions at once, for example in a single
+/// database transaction. It takes the place of the
+/// [`TransactionHandler`] when it is set.
+#[allow(non_camel_case_types)]
+#[async_trait]
+pub trait BatchTransactionHandler<STATE>: Send + Sync + 'static {
+    async fn handle_batch(
+        &self,
+        input: BatchTransactionHandlerContext<'_, STATE>,
+    ) -> Result<(), TransactionHandlerError>;
+}
+
+#[allow(non_camel_case_types)]
+/// A struct that holds the context for a batch transaction handler.
+///
+/// The batch holds consecutive transactions, including transactions without
+/// handled events. `event_processors` holds the [`EventProcessor`] for the
+/// transaction at the same index, which can be used like this:
+/// ```ignore
+/// for event_processor in context.event_processors {
+///     event_processor
+///         .process_events(
+///             context.state,
+///             context.handler_registry,
+///             &mut transaction_context,
+///         )
+///         .await?;
+/// }
+/// ```
+pub struct BatchTransactionHandlerContext<'a, STATE> {
+    pub state: &'a mut STATE,
+    pub transactions: &'a [Transaction],
+    pub event_processors: &'a [EventProcessor<'a>],
+    pub handler_registry: &'a mut HandlerRegistry,
+}
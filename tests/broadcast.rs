@@ -0,0 +1,59 @@
+//! Tests that the subscribers of a broadcast don't hold each other back.
+#![cfg(feature = "channel")]
+
+use radix_event_stream::{
+    models::Transaction,
+    sources::{
+        broadcast::TransactionBroadcast, channel::ChannelTransactionStream,
+    },
+    stream::TransactionStream,
+};
+use std::time::Duration;
+use tokio::sync::mpsc::Receiver;
+
+fn transaction(state_version: u64) -> Transaction {
+    Transaction {
+        state_version,
+        ..Default::default()
+    }
+}
+
+/// Receives the next transaction, failing the test when it doesn't come.
+async fn next(receiver: &mut Receiver<Transaction>) -> Option<u64> {
+    tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("A transaction should have been received")
+        .map(|transaction| transaction.state_version)
+}
+
+#[tokio::test]
+async fn subscriber_dropped_before_starting_doesnt_block_others() {
+    let (upstream, sender) = ChannelTransactionStream::new(10);
+    let broadcast = TransactionBroadcast::new(upstream);
+    let mut started = broadcast.subscribe();
+    let never_started = broadcast.subscribe();
+    let mut receiver = started.start().await.unwrap();
+
+    drop(never_started);
+    sender.send(transaction(1)).await.unwrap();
+
+    assert_eq!(next(&mut receiver).await, Some(1));
+}
+
+#[tokio::test]
+async fn subscriber_dropped_after_starting_doesnt_block_others() {
+    let (upstream, sender) = ChannelTransactionStream::new(10);
+    let broadcast = TransactionBroadcast::new(upstream);
+    let mut first = broadcast.subscribe();
+    let mut second = broadcast.subscribe();
+    let mut receiver = first.start().await.unwrap();
+    let second_receiver = second.start().await.unwrap();
+
+    drop(second_receiver);
+    drop(second);
+    sender.send(transaction(1)).await.unwrap();
+    sender.send(transaction(2)).await.unwrap();
+
+    assert_eq!(next(&mut receiver).await, Some(1));
+    assert_eq!(next(&mut receiver).await, Some(2));
+}
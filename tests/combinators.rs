@@ -0,0 +1,306 @@
+//! Tests what the combinators emit, and what they pass
+//! on to the streams they wrap.
+#![cfg(feature = "channel")]
+
+use async_trait::async_trait;
+use radix_event_stream::{
+    combinators::TransactionStreamExt, models::Transaction,
+    sources::channel::ChannelTransactionStream, stream::TransactionStream,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::sync::mpsc::{Receiver, Sender};
+
+fn transaction(state_version: u64) -> Transaction {
+    Transaction {
+        state_version,
+        ..Default::default()
+    }
+}
+
+/// A channel stream that emits the transactions at these state
+/// versions, and keeps the sender so the stream doesn't end by itself.
+fn open_source(
+    state_versions: &[u64],
+) -> (ChannelTransactionStream, Sender<Transaction>) {
+    let (stream, sender) = ChannelTransactionStream::new(16);
+    for &state_version in state_versions {
+        sender.try_send(transaction(state_version)).unwrap();
+    }
+    (stream, sender)
+}
+
+/// A channel stream that emits the transactions
+/// at these state versions and then ends.
+fn source(state_versions: &[u64]) -> ChannelTransactionStream {
+    open_source(state_versions).0
+}
+
+/// Wraps a stream and records the calls that are passed on to it.
+#[derive(Debug)]
+struct Probe<S> {
+    stream: S,
+    calls: Arc<Mutex<Vec<String>>>,
+    fails_to_start: bool,
+    error: Option<&'static str>,
+    reached_state_version: Option<u64>,
+}
+
+impl<S> Probe<S> {
+    fn new(stream: S) -> Self {
+        Self {
+            stream,
+            calls: Arc::new(Mutex::new(Vec::new())),
+            fails_to_start: false,
+            error: None,
+            reached_state_version: None,
+        }
+    }
+
+    /// The calls that were passed on to the stream so far.
+    fn calls(&self) -> Arc<Mutex<Vec<String>>> {
+        self.calls.clone()
+    }
+
+    fn record(&self, call: String) {
+        self.calls.lock().unwrap().push(call);
+    }
+}
+
+#[async_trait]
+impl<S: TransactionStream + Send> TransactionStream for Probe<S> {
+    async fn start(&mut self) -> Result<Receiver<Transaction>, anyhow::Error> {
+        self.record("start".to_string());
+        if self.fails_to_start {
+            return Err(anyhow::anyhow!("Failed to start"));
+        }
+        self.stream.start().await
+    }
+
+    async fn stop(&mut self) {
+        self.record("stop".to_string());
+        self.stream.stop().await;
+    }
+
+    fn resume_from_state_version(&mut self, state_version: u64) {
+        self.record(format!("resume {}", state_version));
+    }
+
+    fn take_error(&mut self) -> Option<anyhow::Error> {
+        self.error.take().map(anyhow::Error::msg)
+    }
+
+    fn reached_state_version(&self) -> Option<u64> {
+        self.reached_state_version
+    }
+}
+
+/// Receives the next transaction, failing the test when the
+/// stream neither emits one nor ends.
+async fn next(receiver: &mut Receiver<Transaction>) -> Option<u64> {
+    tokio::time::timeout(Duration::from_secs(5), receiver.recv())
+        .await
+        .expect("The stream should have emitted a transaction or ended")
+        .map(|transaction| transaction.state_version)
+}
+
+/// Runs the stream until it ends, and returns the
+/// state versions of the transactions it emitted.
+async fn collect(mut stream: impl TransactionStream) -> Vec<u64> {
+    let mut receiver = stream.start().await.unwrap();
+    let mut state_versions = Vec::new();
+    while let Some(state_version) = next(&mut receiver).await {
+        state_versions.push(state_version);
+    }
+    state_versions
+}
+
+#[tokio::test]
+async fn filter_emits_matching_transactions() {
+    let stream = source(&[1, 2, 3, 4, 5])
+        .filter(|transaction| transaction.state_version % 2 == 0);
+
+    assert_eq!(collect(stream).await, vec![2, 4]);
+}
+
+#[tokio::test]
+async fn map_transforms_transactions() {
+    let stream = source(&[1, 2, 3]).map(|mut transaction| {
+        transaction.state_version *= 10;
+        transaction
+    });
+
+    assert_eq!(collect(stream).await, vec![10, 20, 30]);
+}
+
+#[tokio::test]
+async fn tap_sees_every_transaction() {
+    let tapped = Arc::new(Mutex::new(Vec::new()));
+    let tap_log = tapped.clone();
+    let stream = source(&[1, 2, 3]).tap(move |transaction| {
+        tap_log.lock().unwrap().push(transaction.state_version)
+    });
+
+    assert_eq!(collect(stream).await, vec![1, 2, 3]);
+    assert_eq!(*tapped.lock().unwrap(), vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn skip_until_emits_everything_from_the_first_match() {
+    let stream = source(&[1, 2, 3, 4, 5])
+        .skip_until(|transaction| transaction.state_version % 3 == 0);
+
+    // 4 and 5 don't match, but come after the first match.
+    assert_eq!(collect(stream).await, vec![3, 4, 5]);
+}
+
+#[tokio::test]
+async fn skip_until_without_a_match_emits_nothing() {
+    let stream = source(&[1, 2, 3]).skip_until(|_| false);
+
+    assert!(collect(stream).await.is_empty());
+}
+
+#[tokio::test]
+async fn skip_until_with_a_matching_first_transaction_emits_everything() {
+    let stream = source(&[1, 2, 3])
+        .skip_until(|transaction| transaction.state_version == 1);
+
+    assert_eq!(collect(stream).await, vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn take_until_state_version_ends_after_that_transaction() {
+    let (source, _sender) = open_source(&[1, 2, 3, 4, 5]);
+    let stream = source.take_until_state_version(3);
+
+    assert_eq!(collect(stream).await, vec![1, 2, 3]);
+}
+
+#[tokio::test]
+async fn take_until_state_version_ends_at_a_later_transaction() {
+    let (source, _sender) = open_source(&[1, 2, 4, 5]);
+    let stream = source.take_until_state_version(3);
+
+    assert_eq!(collect(stream).await, vec![1, 2]);
+}
+
+#[tokio::test]
+async fn take_until_state_version_ends_with_the_wrapped_stream() {
+    let stream = source(&[1, 2]).take_until_state_version(5);
+
+    assert_eq!(collect(stream).await, vec![1, 2]);
+}
+
+#[tokio::test]
+async fn take_until_state_version_caps_the_reached_state_version() {
+    let mut probe = Probe::new(source(&[]));
+    probe.reached_state_version = Some(10);
+    let stream = probe.take_until_state_version(5);
+
+    assert_eq!(stream.reached_state_version(), Some(5));
+}
+
+#[tokio::test]
+async fn throttle_spaces_out_transactions() {
+    let stream = source(&[1, 2, 3]).throttle(Duration::from_millis(50));
+    let started = Instant::now();
+
+    assert_eq!(collect(stream).await, vec![1, 2, 3]);
+    // The first transaction is emitted right away.
+    assert!(started.elapsed() >= Duration::from_millis(100));
+}
+
+#[tokio::test]
+async fn chain_starts_the_second_stream_once_the_first_ended() {
+    let (first, first_sender) = open_source(&[1, 2]);
+    let second = Probe::new(source(&[3, 4]));
+    let second_calls = second.calls();
+    let mut stream = first.chain(second);
+    let mut receiver = stream.start().await.unwrap();
+
+    assert_eq!(next(&mut receiver).await, Some(1));
+    assert_eq!(next(&mut receiver).await, Some(2));
+    assert!(second_calls.lock().unwrap().is_empty());
+
+    drop(first_sender);
+
+    assert_eq!(next(&mut receiver).await, Some(3));
+    assert_eq!(next(&mut receiver).await, Some(4));
+    assert_eq!(next(&mut receiver).await, None);
+    assert!(stream.take_error().is_none());
+}
+
+#[tokio::test]
+async fn chain_skips_the_second_stream_when_the_first_fails() {
+    let mut first = Probe::new(source(&[1]));
+    first.error = Some("The first stream gave up");
+    let second = Probe::new(source(&[2]));
+    let second_calls = second.calls();
+    let mut stream = first.chain(second);
+    let mut receiver = stream.start().await.unwrap();
+
+    assert_eq!(next(&mut receiver).await, Some(1));
+    assert_eq!(next(&mut receiver).await, None);
+    assert!(stream.take_error().is_some());
+    assert!(second_calls.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn chain_fails_when_the_second_stream_fails_to_start() {
+    let mut second = Probe::new(source(&[2]));
+    second.fails_to_start = true;
+    let mut stream = source(&[1]).chain(second);
+    let mut receiver = stream.start().await.unwrap();
+
+    assert_eq!(next(&mut receiver).await, Some(1));
+    assert_eq!(next(&mut receiver).await, None);
+    assert!(stream.take_error().is_some());
+}
+
+#[tokio::test]
+async fn chain_passes_stop_and_resume_on_to_both_streams() {
+    let first = Probe::new(source(&[]));
+    let first_calls = first.calls();
+    let second = Probe::new(source(&[]));
+    let second_calls = second.calls();
+    let mut stream = first.chain(second);
+
+    stream.resume_from_state_version(7);
+    stream.stop().await;
+
+    assert_eq!(*first_calls.lock().unwrap(), vec!["resume 7", "stop"]);
+    assert_eq!(*second_calls.lock().unwrap(), vec!["resume 7", "stop"]);
+}
+
+/// Checks that the combinator passes resuming, starting, stopping and
+/// the error of the wrapped stream on to it.
+async fn check_passed_on<S: TransactionStream>(
+    wrap: impl FnOnce(Probe<ChannelTransactionStream>) -> S,
+) {
+    let (source, _sender) = open_source(&[]);
+    let mut probe = Probe::new(source);
+    probe.error = Some("The wrapped stream gave up");
+    let calls = probe.calls();
+    let mut stream = wrap(probe);
+
+    stream.resume_from_state_version(7);
+    let receiver = stream.start().await.unwrap();
+    stream.stop().await;
+    drop(receiver);
+
+    assert_eq!(*calls.lock().unwrap(), vec!["resume 7", "start", "stop"]);
+    assert!(stream.take_error().is_some());
+}
+
+#[tokio::test]
+async fn combinators_pass_stop_and_resume_on_to_the_wrapped_stream() {
+    check_passed_on(|probe| probe.filter(|_| true)).await;
+    check_passed_on(|probe| probe.map(|transaction| transaction)).await;
+    check_passed_on(|probe| probe.tap(|_| {})).await;
+    check_passed_on(|probe| probe.skip_until(|_| true)).await;
+    check_passed_on(|probe| probe.take_until_state_version(10)).await;
+    check_passed_on(|probe| probe.throttle(Duration::from_millis(10))).await;
+}
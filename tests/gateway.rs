@@ -0,0 +1,393 @@
+//! Tests the requests the Gateway stream sends and how it deals
+//! with failing ones, against a mock Gateway on a local port.
+#![cfg(feature = "gateway")]
+
+use radix_event_stream::{
+    retry::RetryPolicy,
+    sources::gateway::{
+        client::GatewayResponseError, filters::GatewayStreamFilters,
+        GatewayTransactionStream,
+    },
+    stream::TransactionStream,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+};
+
+const EMPTY_PAGE: &str = r#"{ "items": [] }"#;
+
+const GATEWAY_STATUS: &str = r#"{
+    "ledger_state": {
+        "state_version": 10,
+        "proposer_round_timestamp": "2024-01-01T00:00:00Z"
+    }
+}"#;
+
+/// The response to looking up the state version at a timestamp,
+/// when no transaction was confirmed after it yet.
+const LOOKUP_PAGE: &str = r#"{
+    "ledger_state": {
+        "state_version": 10,
+        "proposer_round_timestamp": "2024-01-01T00:00:00Z"
+    },
+    "items": []
+}"#;
+
+/// A response of the mock Gateway.
+struct Response {
+    status: u16,
+    headers: Vec<(&'static str, &'static str)>,
+    body: &'static str,
+}
+
+impl Response {
+    fn ok(body: &'static str) -> Self {
+        Self {
+            status: 200,
+            headers: Vec::new(),
+            body,
+        }
+    }
+
+    fn error(status: u16) -> Self {
+        Self {
+            status,
+            headers: Vec::new(),
+            body: r#"{ "message": "error" }"#,
+        }
+    }
+
+    fn header(mut self, name: &'static str, value: &'static str) -> Self {
+        self.headers.push((name, value));
+        self
+    }
+}
+
+/// A request received by the mock Gateway.
+struct Request {
+    path: String,
+    body: String,
+    time: Instant,
+}
+
+/// Responds to the request for a path, given the number
+/// of earlier requests for that path.
+type Responder = dyn Fn(&str, usize) -> Response + Send + Sync;
+
+/// A Gateway that responds to every request with its responder,
+/// and keeps track of the requests it received.
+struct MockGateway {
+    url: String,
+    requests: Arc<Mutex<Vec<Request>>>,
+}
+
+impl MockGateway {
+    async fn start(
+        responder: impl Fn(&str, usize) -> Response + Send + Sync + 'static,
+    ) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let responder: Arc<Responder> = Arc::new(responder);
+        let connection_requests = requests.clone();
+        tokio::spawn(async move {
+            while let Ok((socket, _)) = listener.accept().await {
+                tokio::spawn(serve(
+                    socket,
+                    responder.clone(),
+                    connection_requests.clone(),
+                ));
+            }
+        });
+        Self { url, requests }
+    }
+
+    /// The paths of the requests received so far.
+    fn paths(&self) -> Vec<String> {
+        let requests = self.requests.lock().unwrap();
+        requests
+            .iter()
+            .map(|request| request.path.clone())
+            .collect()
+    }
+
+    /// The bodies of the requests for `path`.
+    fn bodies(&self, path: &str) -> Vec<String> {
+        let requests = self.requests.lock().unwrap();
+        requests
+            .iter()
+            .filter(|request| request.path == path)
+            .map(|request| request.body.clone())
+            .collect()
+    }
+
+    /// The times at which requests for `path` were received.
+    fn times(&self, path: &str) -> Vec<Instant> {
+        let requests = self.requests.lock().unwrap();
+        requests
+            .iter()
+            .filter(|request| request.path == path)
+            .map(|request| request.time)
+            .collect()
+    }
+}
+
+/// Answers the requests on a connection until it's closed.
+async fn serve(
+    mut socket: TcpStream,
+    responder: Arc<Responder>,
+    requests: Arc<Mutex<Vec<Request>>>,
+) {
+    let mut buffer = Vec::new();
+    loop {
+        let header_end = loop {
+            if let Some(index) =
+                buffer.windows(4).position(|window| window == b"\r\n\r\n")
+            {
+                break index + 4;
+            }
+            match socket.read_buf(&mut buffer).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+        };
+        let head = String::from_utf8_lossy(&buffer[..header_end]).to_string();
+        let path = head.split(' ').nth(1).unwrap_or_default().to_string();
+        let content_length = head
+            .lines()
+            .filter_map(|line| line.split_once(':'))
+            .find(|(name, _)| name.eq_ignore_ascii_case("content-length"))
+            .map_or(0, |(_, value)| value.trim().parse().unwrap());
+        while buffer.len() < header_end + content_length {
+            match socket.read_buf(&mut buffer).await {
+                Ok(0) | Err(_) => return,
+                Ok(_) => {}
+            }
+        }
+        let body = String::from_utf8_lossy(
+            &buffer[header_end..header_end + content_length],
+        )
+        .to_string();
+        buffer.drain(..header_end + content_length);
+        let response = {
+            let mut requests = requests.lock().unwrap();
+            let count = requests
+                .iter()
+                .filter(|request| request.path == path)
+                .count();
+            requests.push(Request {
+                path: path.clone(),
+                body,
+                time: Instant::now(),
+            });
+            responder(&path, count)
+        };
+        let mut message = format!(
+            "HTTP/1.1 {} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\n",
+            response.status,
+            response.body.len()
+        );
+        for (name, value) in response.headers {
+            message.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        message.push_str("\r\n");
+        message.push_str(response.body);
+        if socket.write_all(message.as_bytes()).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// A Gateway that serves an empty ledger page and a ledger state
+/// past the end of the stream, so the stream ends right away. It fetches
+/// the page once more after the ledger state, before it ends.
+fn healthy(path: &str, _: usize) -> Response {
+    match path {
+        "/status/gateway-status" => Response::ok(GATEWAY_STATUS),
+        _ => Response::ok(EMPTY_PAGE),
+    }
+}
+
+/// A stream that ends once the Gateway has served a page,
+/// retrying quickly so the tests don't wait on the backoff.
+fn stream(gateway: &MockGateway) -> GatewayTransactionStream {
+    GatewayTransactionStream::new()
+        .gateway_url(gateway.url.clone())
+        .to_state_version(1)
+        .resolve_blueprints(false)
+        .caught_up_timeout(Duration::from_millis(10))
+        .retry_policy(RetryPolicy::fixed(Duration::from_millis(10)))
+}
+
+/// Runs the stream until it ends, and returns the error it gave up on.
+async fn run(stream: &mut GatewayTransactionStream) -> Option<anyhow::Error> {
+    let ended = tokio::time::timeout(Duration::from_secs(10), async {
+        let mut receiver = stream.start().await.unwrap();
+        while receiver.recv().await.is_some() {}
+    })
+    .await;
+    assert!(ended.is_ok(), "The stream should have ended");
+    stream.take_error()
+}
+
+#[tokio::test]
+async fn rate_limited_request_is_retried_after_requested_delay() {
+    let gateway = MockGateway::start(|path, count| match (path, count) {
+        ("/stream/transactions", 0) => {
+            Response::error(429).header("Retry-After", "1")
+        }
+        _ => healthy(path, count),
+    })
+    .await;
+
+    let error = run(&mut stream(&gateway)).await;
+
+    assert!(error.is_none(), "Unexpected error: {:?}", error);
+    let times = gateway.times("/stream/transactions");
+    assert_eq!(times.len(), 3);
+    assert!(times[1] - times[0] >= Duration::from_millis(950));
+}
+
+#[tokio::test]
+async fn huge_requested_delay_is_cut_down() {
+    let gateway = MockGateway::start(|path, count| match (path, count) {
+        ("/stream/transactions", 0) => {
+            Response::error(429).header("Retry-After", "18446744073709551615")
+        }
+        _ => healthy(path, count),
+    })
+    .await;
+
+    let error =
+        run(&mut stream(&gateway)
+            .max_requested_delay(Duration::from_millis(200)))
+        .await;
+
+    assert!(error.is_none(), "Unexpected error: {:?}", error);
+    let times = gateway.times("/stream/transactions");
+    assert_eq!(times.len(), 3);
+    assert!(times[1] - times[0] >= Duration::from_millis(150));
+}
+
+#[tokio::test]
+async fn server_error_fails_over_to_fallback_gateway() {
+    let primary = MockGateway::start(|_, _| Response::error(503)).await;
+    let fallback = MockGateway::start(healthy).await;
+
+    let error =
+        run(&mut stream(&primary)
+            .fallback_gateway_urls(vec![fallback.url.clone()]))
+        .await;
+
+    assert!(error.is_none(), "Unexpected error: {:?}", error);
+    assert_eq!(primary.paths(), vec!["/stream/transactions"]);
+    assert_eq!(
+        fallback.paths(),
+        vec![
+            "/stream/transactions",
+            "/status/gateway-status",
+            "/stream/transactions"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn permanent_error_ends_stream_with_error() {
+    let primary = MockGateway::start(|_, _| Response::error(400)).await;
+    let fallback = MockGateway::start(|_, _| Response::error(400)).await;
+
+    let error =
+        run(&mut stream(&primary)
+            .fallback_gateway_urls(vec![fallback.url.clone()]))
+        .await
+        .expect("The stream should end with the error it gave up on");
+
+    // Rejected requests aren't retried against the same Gateway.
+    assert_eq!(primary.paths(), vec!["/stream/transactions"]);
+    assert_eq!(fallback.paths(), vec!["/stream/transactions"]);
+    let error = error.downcast_ref::<GatewayResponseError>().unwrap();
+    assert_eq!(error.status.as_u16(), 400);
+}
+
+/// Filters on `count` made up emitters.
+fn emitter_filters(count: usize) -> GatewayStreamFilters {
+    GatewayStreamFilters {
+        event_global_emitters: (0..count)
+            .map(|index| format!("component_sim1emitter{}", index))
+            .collect(),
+        ..Default::default()
+    }
+}
+
+#[tokio::test]
+async fn few_emitters_are_fetched_separately() {
+    let gateway = MockGateway::start(healthy).await;
+
+    let error = run(&mut stream(&gateway).filters(emitter_filters(2))).await;
+
+    assert!(error.is_none(), "Unexpected error: {:?}", error);
+    let bodies = gateway.bodies("/stream/transactions");
+    assert_eq!(bodies.len(), 2);
+    assert!(bodies[0].contains(r#"["component_sim1emitter0"]"#));
+    assert!(bodies[1].contains(r#"["component_sim1emitter1"]"#));
+}
+
+#[tokio::test]
+async fn many_emitters_are_fetched_without_emitter_filter() {
+    let gateway = MockGateway::start(healthy).await;
+
+    let error = run(&mut stream(&gateway)
+        .filters(emitter_filters(3))
+        .max_emitter_filters(2))
+    .await;
+
+    assert!(error.is_none(), "Unexpected error: {:?}", error);
+    let bodies = gateway.bodies("/stream/transactions");
+    assert_eq!(bodies.len(), 2);
+    assert!(!bodies[0].contains("event_global_emitters_filter"));
+}
+
+#[tokio::test]
+async fn timestamp_lookup_fails_over_to_fallback_gateway() {
+    let primary = MockGateway::start(|_, _| Response::error(503)).await;
+    let fallback = MockGateway::start(|path, count| match (path, count) {
+        ("/stream/transactions", 0) => Response::ok(LOOKUP_PAGE),
+        _ => healthy(path, count),
+    })
+    .await;
+    let mut stream = stream(&primary)
+        .fallback_gateway_urls(vec![fallback.url.clone()])
+        .from_timestamp("2024-01-01T00:00:00Z".parse().unwrap());
+
+    let error = run(&mut stream).await;
+
+    assert!(error.is_none(), "Unexpected error: {:?}", error);
+    // Without transactions at or after the timestamp,
+    // the stream starts at the next one.
+    assert_eq!(stream.resolved_from_state_version(), Some(11));
+    assert_eq!(primary.paths(), vec!["/stream/transactions"]);
+}
+
+#[tokio::test]
+async fn stopping_interrupts_timestamp_lookup() {
+    let gateway = MockGateway::start(|_, _| Response::error(503)).await;
+    let mut stream = stream(&gateway)
+        .from_timestamp("2024-01-01T00:00:00Z".parse().unwrap());
+
+    let mut receiver =
+        tokio::time::timeout(Duration::from_secs(5), stream.start())
+            .await
+            .expect("Starting shouldn't wait for the lookup")
+            .unwrap();
+    stream.stop().await;
+
+    let ended =
+        tokio::time::timeout(Duration::from_secs(5), receiver.recv()).await;
+    assert!(matches!(ended, Ok(None)), "The stream should have ended");
+    assert_eq!(stream.resolved_from_state_version(), None);
+}
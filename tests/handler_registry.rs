@@ -0,0 +1,127 @@
+//! Tests which handlers the registry resolves for an event, and that the
+//! settings of a matcher come from the same matcher as its handlers.
+#![cfg(feature = "gateway")]
+
+use async_trait::async_trait;
+use radix_client::gateway::models::{EntityType, ModuleId};
+use radix_event_stream::{
+    error::EventHandlerError,
+    event_handler::{EventHandler, EventHandlerContext, HandlerRegistry},
+    event_matcher::EventMatcher,
+    models::{Event, EventEmitter, Transaction, TransactionStatus},
+    native_events::{fungible_vault::FungibleVaultEventType, NativeEventType},
+    retry::RetryPolicy,
+};
+use std::time::Duration;
+
+const COMPONENT: &str = "component_sim1swap";
+const VAULT: &str = "internal_vault_sim1fees";
+
+/// A handler that does nothing, registered to check how handlers resolve.
+#[derive(Clone)]
+struct NoopHandler;
+
+#[async_trait]
+impl EventHandler<(), ()> for NoopHandler {
+    async fn handle(
+        &self,
+        _: EventHandlerContext<'_, (), ()>,
+        _: &[u8],
+    ) -> Result<(), EventHandlerError> {
+        Ok(())
+    }
+}
+
+fn event(entity_address: &str, entity_type: EntityType, name: &str) -> Event {
+    Event {
+        name: name.to_string(),
+        binary_sbor_data: Vec::new(),
+        json_sbor_data: None,
+        emitter: EventEmitter::Method {
+            entity_address: entity_address.to_string(),
+            entity_type,
+            is_global: true,
+            object_module_id: ModuleId::Main,
+            blueprint: None,
+        },
+    }
+}
+
+fn failed_transaction() -> Transaction {
+    Transaction {
+        status: TransactionStatus::CommittedFailure,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn settings_come_from_the_matcher_of_the_resolved_handlers() {
+    let mut registry = HandlerRegistry::new();
+    registry.add_handler_matching(EventMatcher::name("SwapEvent"), NoopHandler);
+    registry.set_handles_failed_transactions_matching(
+        EventMatcher::name("SwapEvent"),
+        true,
+    );
+    registry.set_retry_policy_matching(
+        EventMatcher::name("SwapEvent"),
+        RetryPolicy::fixed(Duration::from_secs(1)),
+    );
+    let exact = registry.add_handler(COMPONENT, "SwapEvent", NoopHandler);
+    let swap =
+        event(COMPONENT, EntityType::GlobalGenericComponent, "SwapEvent");
+
+    // The exact handler handles the event, and it has no settings.
+    assert!(!registry.handles_event(&failed_transaction(), &swap));
+    assert!(!registry.handles_failed_transactions(&swap));
+    assert_eq!(registry.retry_policy(&swap), None);
+
+    registry.remove_handler(exact);
+
+    assert!(registry.handles_event(&failed_transaction(), &swap));
+    assert_eq!(
+        registry.retry_policy(&swap),
+        Some(&RetryPolicy::fixed(Duration::from_secs(1)))
+    );
+}
+
+#[test]
+fn settings_apply_to_handlers_added_later() {
+    let mut registry = HandlerRegistry::new();
+    registry.set_handles_failed_transactions(COMPONENT, "SwapEvent", true);
+    let swap =
+        event(COMPONENT, EntityType::GlobalGenericComponent, "SwapEvent");
+
+    assert!(!registry.handler_exists(&swap));
+
+    registry.add_handler(COMPONENT, "SwapEvent", NoopHandler);
+
+    assert!(registry.handles_event(&failed_transaction(), &swap));
+}
+
+#[test]
+fn native_handlers_go_before_matchers_for_native_events() {
+    let mut registry = HandlerRegistry::new();
+    let native = registry.add_native_handler(
+        NativeEventType::FungibleVault(FungibleVaultEventType::PayFeeEvent),
+        NoopHandler,
+    );
+    let exact = registry.add_handler(VAULT, "PayFeeEvent", NoopHandler);
+    let fee_event =
+        event(VAULT, EntityType::InternalFungibleVault, "PayFeeEvent");
+
+    let handlers: Vec<_> = registry
+        .resolve_handlers::<(), ()>(&fee_event)
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    assert_eq!(handlers, vec![native]);
+
+    registry.remove_handler(native);
+
+    let handlers: Vec<_> = registry
+        .resolve_handlers::<(), ()>(&fee_event)
+        .into_iter()
+        .map(|(id, _)| id)
+        .collect();
+    assert_eq!(handlers, vec![exact]);
+}
@@ -0,0 +1,140 @@
+//! Tests where the hybrid stream continues live after the backfill.
+
+use async_trait::async_trait;
+use radix_event_stream::{
+    models::Transaction, sources::hybrid::HybridTransactionStream,
+    stream::TransactionStream,
+};
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+use tokio::sync::mpsc::Receiver;
+
+/// A stream that emits a fixed list of transactions and then ends,
+/// reporting a fixed state version as the one it reached.
+#[derive(Debug, Default)]
+struct ScriptedStream {
+    state_versions: Vec<u64>,
+    reached_state_version: Option<u64>,
+    /// The state version the stream was resumed from, each time it started.
+    starts: Arc<Mutex<Vec<Option<u64>>>>,
+    resumed_from: Option<u64>,
+}
+
+impl ScriptedStream {
+    fn new(state_versions: Vec<u64>) -> Self {
+        Self {
+            state_versions,
+            ..Default::default()
+        }
+    }
+
+    fn reached(mut self, state_version: u64) -> Self {
+        self.reached_state_version = Some(state_version);
+        self
+    }
+}
+
+#[async_trait]
+impl TransactionStream for ScriptedStream {
+    async fn start(&mut self) -> Result<Receiver<Transaction>, anyhow::Error> {
+        self.starts.lock().unwrap().push(self.resumed_from);
+        let (tx, rx) = tokio::sync::mpsc::channel(16);
+        for &state_version in &self.state_versions {
+            tx.send(Transaction {
+                state_version,
+                ..Default::default()
+            })
+            .await?;
+        }
+        Ok(rx)
+    }
+
+    async fn stop(&mut self) {}
+
+    fn resume_from_state_version(&mut self, state_version: u64) {
+        self.resumed_from = Some(state_version);
+    }
+
+    fn reached_state_version(&self) -> Option<u64> {
+        self.reached_state_version
+    }
+}
+
+/// Runs the stream until it ends, and returns the state versions
+/// it emitted and the error it ended with.
+async fn run(
+    stream: &mut impl TransactionStream,
+) -> (Vec<u64>, Option<anyhow::Error>) {
+    let state_versions = tokio::time::timeout(Duration::from_secs(5), async {
+        let mut receiver = stream.start().await.unwrap();
+        let mut state_versions = Vec::new();
+        while let Some(transaction) = receiver.recv().await {
+            state_versions.push(transaction.state_version);
+        }
+        state_versions
+    })
+    .await
+    .expect("The stream should have ended");
+    (state_versions, stream.take_error())
+}
+
+#[tokio::test]
+async fn live_continues_after_the_state_version_the_backfill_reached() {
+    let live = ScriptedStream::new(vec![11, 12]);
+    let live_starts = live.starts.clone();
+    let mut stream = HybridTransactionStream::new(
+        ScriptedStream::new(vec![1, 2]).reached(10),
+        live,
+    );
+
+    let (state_versions, error) = run(&mut stream).await;
+
+    assert!(error.is_none(), "Unexpected error: {:?}", error);
+    assert_eq!(state_versions, vec![1, 2, 11, 12]);
+    assert_eq!(*live_starts.lock().unwrap(), vec![Some(11)]);
+}
+
+#[tokio::test]
+async fn live_continues_after_the_last_transaction_of_the_backfill() {
+    let live = ScriptedStream::new(vec![3]);
+    let live_starts = live.starts.clone();
+    let mut stream =
+        HybridTransactionStream::new(ScriptedStream::new(vec![1, 2]), live);
+
+    let (state_versions, error) = run(&mut stream).await;
+
+    assert!(error.is_none(), "Unexpected error: {:?}", error);
+    assert_eq!(state_versions, vec![1, 2, 3]);
+    assert_eq!(*live_starts.lock().unwrap(), vec![Some(3)]);
+}
+
+#[tokio::test]
+async fn empty_backfill_without_from_state_version_fails() {
+    let live = ScriptedStream::new(vec![1]);
+    let live_starts = live.starts.clone();
+    let mut stream =
+        HybridTransactionStream::new(ScriptedStream::new(Vec::new()), live);
+
+    let (state_versions, error) = run(&mut stream).await;
+
+    assert!(state_versions.is_empty());
+    assert!(error.is_some(), "The stream should have failed");
+    assert!(live_starts.lock().unwrap().is_empty());
+}
+
+#[tokio::test]
+async fn empty_backfill_continues_at_from_state_version() {
+    let live = ScriptedStream::new(vec![5]);
+    let live_starts = live.starts.clone();
+    let mut stream =
+        HybridTransactionStream::new(ScriptedStream::new(Vec::new()), live)
+            .from_state_version(5);
+
+    let (state_versions, error) = run(&mut stream).await;
+
+    assert!(error.is_none(), "Unexpected error: {:?}", error);
+    assert_eq!(state_versions, vec![5]);
+    assert_eq!(*live_starts.lock().unwrap(), vec![Some(5)]);
+}
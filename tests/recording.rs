@@ -0,0 +1,150 @@
+//! Tests appending to a recording that ends in a partially
+//! written transaction.
+#![cfg(all(feature = "file", feature = "channel"))]
+
+use radix_event_stream::{
+    models::Transaction,
+    sources::{
+        channel::ChannelTransactionStream, file::FileTransactionStream,
+        recording::RecordingStream,
+    },
+    stream::TransactionStream,
+};
+use std::{
+    fs::OpenOptions,
+    ops::RangeInclusive,
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// A directory of its own for each test, which is emptied first.
+fn test_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!(
+        "radix_event_stream_recording_{}_{}",
+        name,
+        std::process::id()
+    ));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// A channel stream that emits the transactions
+/// at these state versions and then ends.
+fn source(state_versions: RangeInclusive<u64>) -> ChannelTransactionStream {
+    let (stream, sender) =
+        ChannelTransactionStream::new(state_versions.clone().count() as u64);
+    for state_version in state_versions {
+        sender
+            .try_send(Transaction {
+                state_version,
+                ..Default::default()
+            })
+            .unwrap();
+    }
+    stream
+}
+
+/// Runs the stream until it ends, and returns the state
+/// versions of the transactions it emitted.
+async fn run(stream: &mut impl TransactionStream) -> Vec<u64> {
+    let state_versions = tokio::time::timeout(Duration::from_secs(5), async {
+        let mut receiver = stream.start().await.unwrap();
+        let mut state_versions = Vec::new();
+        while let Some(transaction) = receiver.recv().await {
+            state_versions.push(transaction.state_version);
+        }
+        state_versions
+    })
+    .await
+    .expect("The stream should have ended");
+    stream.stop().await;
+    assert!(stream.take_error().is_none());
+    state_versions
+}
+
+async fn record(path: &Path, state_versions: RangeInclusive<u64>) {
+    let mut stream =
+        RecordingStream::new(source(state_versions), path).append(true);
+    run(&mut stream).await;
+}
+
+async fn replay(path: &Path, from_state_version: u64) -> Vec<u64> {
+    let mut stream =
+        FileTransactionStream::new(path.to_str().unwrap().to_string())
+            .from_state_version(from_state_version);
+    run(&mut stream).await
+}
+
+/// Cuts the file off at `length`, like a process that was
+/// killed while writing to it.
+fn cut_off(path: &Path, length: u64) {
+    OpenOptions::new()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_len(length)
+        .unwrap();
+}
+
+fn file_length(path: &Path) -> u64 {
+    std::fs::metadata(path).unwrap().len()
+}
+
+#[tokio::test]
+async fn appending_removes_a_partial_line() {
+    let dir = test_dir("partial_line");
+    let path = dir.join("transactions.ndjson");
+    record(&path, 1..=3).await;
+    // The newline is the last byte of the last transaction.
+    cut_off(&path, file_length(&path) - 1);
+
+    record(&path, 3..=4).await;
+
+    assert_eq!(replay(&path, 0).await, vec![1, 2, 3, 4]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn appending_removes_a_partial_record() {
+    let dir = test_dir("partial_record");
+    let path = dir.join("transactions.bin");
+    record(&path, 1..=3).await;
+    cut_off(&path, file_length(&path) - 3);
+
+    record(&path, 3..=4).await;
+
+    assert_eq!(replay(&path, 0).await, vec![1, 2, 3, 4]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn appending_removes_a_partial_indexed_record() {
+    let dir = test_dir("partial_indexed_record");
+    let path = dir.join("transactions.bin");
+    // The index has an entry for the 1st and the 257th transaction.
+    record(&path, 1..=257).await;
+    let index = std::fs::read(dir.join("transactions.bin.idx")).unwrap();
+    assert_eq!(index.len(), 32);
+    let offset = u64::from_le_bytes(index[24..].try_into().unwrap());
+    cut_off(&path, offset + 3);
+
+    record(&path, 257..=260).await;
+
+    assert_eq!(replay(&path, 0).await, (1..=260).collect::<Vec<_>>());
+    assert_eq!(replay(&path, 258).await, vec![258, 259, 260]);
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[tokio::test]
+async fn appending_to_a_partial_compressed_file_fails() {
+    let dir = test_dir("partial_compressed");
+    let path = dir.join("transactions.ndjson.gz");
+    record(&path, 1..=3).await;
+    cut_off(&path, file_length(&path) - 3);
+
+    let mut stream = RecordingStream::new(source(4..=4), &path).append(true);
+
+    assert!(stream.start().await.is_err());
+    std::fs::remove_dir_all(dir).unwrap();
+}